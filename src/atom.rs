@@ -0,0 +1,111 @@
+use crate::{parse_timestamp, MicroblogStruct};
+use chrono::{SecondsFormat, Utc};
+
+const TITLE_LENGTH: usize = 60;
+
+pub struct Feed {
+    pub title: String,
+    pub site_url: String,
+    pub feed_url: String,
+    pub author: String,
+}
+
+/// Renders public, unencrypted posts as an Atom feed (RFC 4287). Everything the RFC requires is
+/// checked up front, so a bad config or an undated post is an error rather than a feed readers
+/// reject.
+pub fn render(
+    posts: &[MicroblogStruct],
+    feed: &Feed,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    for (name, url) in [("site_url", &feed.site_url), ("feed_url", &feed.feed_url)] {
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            return Err(format!(
+                "Feed {} must be an absolute http(s) URL, got '{}'",
+                name, url
+            )
+            .into());
+        }
+    }
+    if feed.author.trim().is_empty() {
+        return Err("Feed author cannot be empty".into());
+    }
+
+    let mut entries = Vec::new();
+    for post in posts
+        .iter()
+        .filter(|post| post.visibility == "public" && !post.encrypted)
+    {
+        let updated = parse_timestamp(&post.time)
+            .ok_or_else(|| format!("Post {} has an unreadable time '{}'", post.id, post.time))?;
+        entries.push((post, updated.to_rfc3339_opts(SecondsFormat::Secs, false)));
+    }
+    // Newest first, which is what readers expect and what `updated` is taken from.
+    entries.reverse();
+
+    let updated = entries
+        .first()
+        .map(|(_, time)| time.clone())
+        .unwrap_or_else(|| Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true));
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("  <id>{}</id>\n", escape(&feed.feed_url)));
+    xml.push_str(&format!("  <title>{}</title>\n", escape(&feed.title)));
+    xml.push_str(&format!("  <updated>{}</updated>\n", updated));
+    xml.push_str(&format!(
+        "  <author><name>{}</name></author>\n",
+        escape(&feed.author)
+    ));
+    xml.push_str(&format!(
+        "  <link rel=\"self\" type=\"application/atom+xml\" href=\"{}\"/>\n",
+        escape(&feed.feed_url)
+    ));
+    xml.push_str(&format!(
+        "  <link rel=\"alternate\" type=\"text/html\" href=\"{}\"/>\n",
+        escape(&feed.site_url)
+    ));
+
+    for (post, time) in entries {
+        xml.push_str("  <entry>\n");
+        // Ids from CSV imports aren't always UUIDs, and an entry id must still be a URI.
+        let id = match uuid::Uuid::parse_str(&post.id) {
+            Ok(uuid) => uuid.urn().to_string(),
+            Err(_) => format!("{}#{}", feed.feed_url, post.id),
+        };
+        xml.push_str(&format!("    <id>{}</id>\n", escape(&id)));
+        xml.push_str(&format!(
+            "    <title>{}</title>\n",
+            escape(&title(&post.content))
+        ));
+        xml.push_str(&format!("    <updated>{}</updated>\n", time));
+        for tag in &post.tags {
+            xml.push_str(&format!("    <category term=\"{}\"/>\n", escape(tag)));
+        }
+        xml.push_str(&format!(
+            "    <content type=\"text\">{}</content>\n",
+            escape(&post.content)
+        ));
+        xml.push_str("  </entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    Ok(xml)
+}
+
+/// Posts have no title, so entries use the start of the first line.
+fn title(content: &str) -> String {
+    let line = content.lines().next().unwrap_or_default();
+    if line.chars().count() > TITLE_LENGTH {
+        let mut title: String = line.chars().take(TITLE_LENGTH - 1).collect();
+        title.push('…');
+        title
+    } else {
+        line.to_string()
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,170 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+pub const TEMPLATE: &str = r#"# adot configuration
+#
+# Environment variables (PROJECT_ID, GOOGLE_APPLICATION_CREDENTIALS, IPINFO_TOKEN,
+# SLACK_WEBHOOK_URL) always take precedence over the values in this file.
+
+# Firebase project that holds the microblog and location collections.
+project_id = "your-project-id"
+
+# Service account key used to talk to Firestore.
+google_application_credentials = "/path/to/service-account.json"
+
+# Token from https://ipinfo.io/account/token, used by `adot location`.
+ipinfo_token = "your-ipinfo-token"
+
+# Incoming webhook that `adot microblog --slack` mirrors posts to (needs the `slack` feature).
+# slack_webhook_url = "https://hooks.slack.com/services/..."
+
+# WASM module that transforms each new post before it is stored (needs the `wasm` feature). It
+# exports `memory`, `alloc(len) -> ptr` and `transform(ptr, len) -> (ptr << 32) | len` over the
+# post's JSON and gets no imports. If it fails, the post is not stored.
+# plugin = "/path/to/transform.wasm"
+
+# Turn length checks such as --max-bytes into warnings, for automated posting that must never fail
+# on content size. It overrides any stricter validation; the --content-length-warning-only flag
+# turns it on for a single run.
+# content_length_warning_only = true
+
+# Remove #words from the content when `adot microblog --extract-hashtags` turns them into tags,
+# instead of only with --strip-hashtags.
+# strip_hashtags = true
+
+# Seconds a single Firestore write may take before adot gives up on it (also --firestore-timeout).
+# A timed-out write counts against the write retry budget. Unset means wait as long as it takes.
+# firestore_timeout = 30
+
+# Collection names, if yours differ from the defaults.
+[collections]
+microblog = "microblog"
+location = "location"
+location_history = "location_history"
+
+# How often failed Firestore calls are retried. Reads are always safe to repeat. A write that
+# times out may still have landed, so retrying it can fail with "already exists" (or, for a
+# location check-in, record it twice), which is why writes are not retried unless you opt in.
+[retries]
+read = 3
+write = 0
+
+# Used by `adot microblog export --format atom`. site_url and author are required; feed_url is
+# where the feed itself is published and defaults to <site_url>/atom.xml.
+[feed]
+# title = "My microblog"
+# site_url = "https://example.com"
+# author = "Your Name"
+# feed_url = "https://example.com/atom.xml"
+
+# Profiles override any of the values above for one environment, selected with ADOT_ENV or --env.
+# An environment without a profile falls back to the values above with its name as a collection
+# prefix (dev_microblog, dev_location).
+#
+# [profiles.dev]
+# project_id = "your-dev-project-id"
+# google_application_credentials = "/path/to/dev-service-account.json"
+#
+# [profiles.dev.collections]
+# microblog = "microblog"
+"#;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Collections {
+    pub microblog: Option<String>,
+    pub location: Option<String>,
+    pub location_history: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Retries {
+    pub read: Option<usize>,
+    pub write: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Feed {
+    pub title: Option<String>,
+    pub site_url: Option<String>,
+    pub feed_url: Option<String>,
+    pub author: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Settings {
+    pub project_id: Option<String>,
+    pub google_application_credentials: Option<String>,
+    pub ipinfo_token: Option<String>,
+    #[cfg_attr(not(feature = "slack"), allow(dead_code))]
+    pub slack_webhook_url: Option<String>,
+    pub plugin: Option<String>,
+    pub content_length_warning_only: Option<bool>,
+    pub strip_hashtags: Option<bool>,
+    pub firestore_timeout: Option<u64>,
+    #[serde(default)]
+    pub collections: Collections,
+    #[serde(default)]
+    pub retries: Retries,
+    #[serde(default)]
+    pub feed: Feed,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    #[serde(flatten)]
+    pub settings: Settings,
+    #[serde(default)]
+    pub profiles: HashMap<String, Settings>,
+}
+
+impl Config {
+    /// Looks a value up in the profile for `adot_env` first, then in the top-level settings.
+    pub fn lookup<T, F>(&self, adot_env: &str, pick: F) -> Option<T>
+    where
+        T: Clone,
+        F: Fn(&Settings) -> Option<&T>,
+    {
+        self.profiles
+            .get(adot_env)
+            .and_then(&pick)
+            .or_else(|| pick(&self.settings))
+            .cloned()
+    }
+}
+
+/// `ADOT_CONFIG` if set, otherwise `$XDG_CONFIG_HOME/adot/config.toml` or
+/// `~/.config/adot/config.toml`.
+pub fn path() -> PathBuf {
+    if let Ok(path) = env::var("ADOT_CONFIG") {
+        return PathBuf::from(path);
+    }
+
+    let base = env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(env::var("HOME").unwrap_or_default()).join(".config"));
+    base.join("adot").join("config.toml")
+}
+
+/// Loads the config file once per run. A missing file is an empty config, a malformed one is an
+/// error.
+pub fn get() -> Result<&'static Config, Box<dyn std::error::Error + Send + Sync>> {
+    static CONFIG: OnceLock<Result<Config, String>> = OnceLock::new();
+
+    let loaded = CONFIG.get_or_init(|| {
+        let path = path();
+        match fs::read_to_string(&path) {
+            Ok(raw) => toml::from_str(&raw)
+                .map_err(|e| format!("Invalid config file {}: {}", path.display(), e)),
+            Err(_) => Ok(Config::default()),
+        }
+    });
+
+    loaded.as_ref().map_err(|e| e.clone().into())
+}
@@ -0,0 +1,82 @@
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+const PREFIX: &str = "v1:";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+) -> Result<Key, Box<dyn std::error::Error + Send + Sync>> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+/// Encrypts `plaintext` with a key derived from `passphrase`. The result is
+/// `v1:` followed by base64 of salt, nonce and ciphertext, so it can be stored as a plain string.
+pub fn encrypt(
+    plaintext: &str,
+    passphrase: &str,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|_| "Encryption failed")?;
+
+    let mut payload = Vec::with_capacity(SALT_LEN + NONCE_LEN + ciphertext.len());
+    payload.extend_from_slice(&salt);
+    payload.extend_from_slice(&nonce);
+    payload.extend_from_slice(&ciphertext);
+    Ok(format!("{}{}", PREFIX, STANDARD.encode(payload)))
+}
+
+pub fn decrypt(
+    encoded: &str,
+    passphrase: &str,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let payload = encoded
+        .strip_prefix(PREFIX)
+        .ok_or("Unsupported encrypted content format")?;
+    let payload = STANDARD.decode(payload)?;
+    if payload.len() < SALT_LEN + NONCE_LEN {
+        return Err("Encrypted content is truncated".into());
+    }
+
+    let (salt, rest) = payload.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, salt)?);
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Wrong passphrase or corrupted content")?;
+
+    Ok(String::from_utf8(plaintext)?)
+}
+
+/// Reads the passphrase from `ADOT_PASSPHRASE`, or asks for it without echoing. New passphrases are
+/// asked for twice so a typo doesn't lock a post away.
+pub fn passphrase(confirm: bool) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    if let Ok(passphrase) = std::env::var("ADOT_PASSPHRASE") {
+        return Ok(passphrase);
+    }
+
+    let passphrase = rpassword::prompt_password("🔑 Passphrase: ")?;
+    if passphrase.is_empty() {
+        return Err("Passphrase cannot be empty".into());
+    }
+    if confirm && rpassword::prompt_password("🔑 Confirm passphrase: ")? != passphrase {
+        return Err("Passphrases do not match".into());
+    }
+    Ok(passphrase)
+}
@@ -1,25 +1,119 @@
-use chrono::Utc;
-use clap::{arg, ArgMatches, Command};
+use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use clap::{arg, Arg, ArgGroup, ArgMatches, Command};
+use firestore::errors::FirestoreError;
 use firestore::*;
-use reqwest;
-use serde::{Deserialize, Serialize};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
 use std::fs;
+use std::io::{self, BufWriter, Read, Write};
+use std::net::IpAddr;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
+mod atom;
+mod config;
+mod crypto;
+#[cfg(feature = "pdf")]
+mod pdf;
+#[cfg(feature = "wasm")]
+mod plugin;
+#[cfg(feature = "slack")]
+mod slack;
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 struct MicroblogStruct {
     id: String,
     content: String,
+    #[serde(deserialize_with = "deserialize_time")]
     time: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default = "default_visibility")]
+    visibility: String,
+    #[serde(default)]
+    encrypted: bool,
+    #[serde(default)]
+    pinned_at: Option<String>,
+    #[serde(default)]
+    location: Option<LocationStruct>,
+    #[serde(default)]
+    reply_to: Option<String>,
+}
+
+const VISIBILITIES: [&str; 3] = ["public", "unlisted", "private"];
+const MICROBLOG_FIELDS: [&str; 9] = [
+    "id",
+    "content",
+    "time",
+    "tags",
+    "visibility",
+    "encrypted",
+    "pinned_at",
+    "location",
+    "reply_to",
+];
+
+fn default_visibility() -> String {
+    "public".to_string()
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 struct TimeStruct {
+    #[serde(deserialize_with = "deserialize_time")]
     utc: String,
 }
 
+/// Older documents may hold `time` as epoch numbers rather than strings, so accept either and
+/// leave the interpretation to `parse_timestamp`.
+fn deserialize_time<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawTime {
+        Text(String),
+        Integer(i64),
+        Float(f64),
+    }
+
+    Ok(match RawTime::deserialize(deserializer)? {
+        RawTime::Text(text) => text,
+        RawTime::Integer(number) => number.to_string(),
+        RawTime::Float(number) => number.to_string(),
+    })
+}
+
+/// Parses a stored timestamp in any format adot has written over time: RFC3339 (current), RFC2822,
+/// and epoch seconds or milliseconds.
+fn parse_timestamp(raw: &str) -> Option<DateTime<FixedOffset>> {
+    let raw = raw.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(raw) {
+        return Some(time);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc2822(raw) {
+        return Some(time);
+    }
+
+    let epoch: f64 = raw.parse().ok()?;
+    // Anything past the year 5138 in seconds is far more likely to be milliseconds.
+    let millis = if epoch.abs() >= 1e11 {
+        epoch
+    } else {
+        epoch * 1000.0
+    };
+    Utc.timestamp_millis_opt(millis as i64)
+        .single()
+        .map(|time| time.fixed_offset())
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 struct LocationStruct {
     city: String,
@@ -27,6 +121,57 @@ struct LocationStruct {
     country: String,
     timezone: String,
     time: TimeStruct,
+    #[serde(default)]
+    transport: Option<String>,
+    #[serde(default)]
+    lat: Option<f64>,
+    #[serde(default)]
+    lon: Option<f64>,
+    #[serde(default)]
+    accuracy: Option<String>,
+    #[serde(default)]
+    org: Option<String>,
+    /// The full ipinfo response, only kept with `--store-raw`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    raw: Option<serde_json::Value>,
+}
+
+/// The flags shared by `microblog` and `microblog continue`, which both create a post.
+fn id_version_arg() -> Arg {
+    arg!(--"id-version" <VERSION> "UUID version used for the document id (7 is time-ordered)")
+        .value_parser(["4", "7"])
+        .default_value("4")
+}
+
+/// A new document id: a time-ordered UUIDv7 for version "7", otherwise a random UUIDv4.
+fn new_post_id(version: &str) -> String {
+    match version {
+        "7" => Uuid::now_v7().to_string(),
+        _ => Uuid::new_v4().to_string(),
+    }
+}
+
+fn post_args() -> Vec<Arg> {
+    vec![
+        arg!([content] "The content of the microblog post").required(true),
+        id_version_arg(),
+        arg!(--raw "Store the content exactly as given, without normalizing whitespace"),
+        arg!(--"max-bytes" <N> "Reject content longer than N bytes of UTF-8 (checked before encryption)")
+            .value_parser(clap::value_parser!(usize)),
+        arg!(--"content-length-warning-only" "Warn instead of failing when a length check fails (or set content_length_warning_only in the config); this beats any strict setting"),
+        arg!(--visibility <VISIBILITY> "Who the post is meant for")
+            .value_parser(VISIBILITIES)
+            .default_value("public"),
+        arg!(--private "Shorthand for --visibility private").conflicts_with("visibility"),
+        arg!(--"with-location" "Attach where you are now to the post (without it if the lookup fails)"),
+        arg!(--"extract-hashtags" "Add #words in the content to the post's tags (lowercased)")
+            .conflicts_with("encrypt"),
+        arg!(--"strip-hashtags" "Also remove the extracted #words from the content (or set strip_hashtags in the config)")
+            .requires("extract-hashtags"),
+        arg!(--encrypt "Encrypt the content with a passphrase before storing it (or set ADOT_PASSPHRASE)"),
+        arg!(--slack "Mirror the post to the configured Slack webhook (needs the `slack` feature)")
+            .conflicts_with("encrypt"),
+    ]
 }
 
 #[tokio::main]
@@ -35,201 +180,3511 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .version("1.0")
         .author("Akshith Garapati")
         .about("CLI tool for microblogging and location tracking")
+        .arg(
+            arg!(--env <ENV> "Environment to use, e.g. dev or prod (overrides ADOT_ENV)")
+                .global(true),
+        )
+        .arg(arg!(-v --verbose "Print the active environment and other details on stderr").global(true))
+        .arg(arg!(--"no-deprecation-warnings" "Don't warn about deprecated flags").global(true))
+        .arg(arg!(--"no-buffer" "Print records as they are written instead of buffering stdout").global(true))
+        .arg(
+            arg!(--"firestore-timeout" <SECONDS> "Give up on a Firestore write after this long (overrides firestore_timeout in the config)")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .global(true),
+        )
         .subcommand(
             Command::new("microblog")
                 .about("Create a new microblog post")
-                .arg(arg!([content] "The content of the microblog post").required(true)),
+                .args_conflicts_with_subcommands(true)
+                .subcommand_negates_reqs(true)
+                .args(post_args())
+                .subcommand(
+                    Command::new("continue")
+                        .about("Post a reply to your most recent post, threading onto it")
+                        .args(post_args()),
+                )
+                .subcommand(
+                    Command::new("replace")
+                        .about("Overwrite an entire microblog document from a JSON blob, keeping its id")
+                        .arg(arg!(<id> "The id of the post to replace"))
+                        .arg(arg!(<json> "The replacement document as JSON, or '-' to read it from stdin"))
+                        .arg(arg!(-y --yes "Apply the replacement without asking for confirmation")),
+                )
+                .subcommand(
+                    Command::new("amend")
+                        .about("Change some fields of a post, leaving the rest as stored")
+                        .arg(arg!(<id> "The id of the post to amend"))
+                        .arg(arg!(--content <CONTENT> "New content (re-encrypted if the post is encrypted)"))
+                        .arg(arg!(--tags <TAGS> "New comma-separated tags, or '' to clear them"))
+                        .group(ArgGroup::new("changes").args(["content", "tags"]).multiple(true).required(true))
+                        .after_help("Only content and tags can be amended; use `adot microblog replace` to rewrite anything else."),
+                )
+                .subcommand(
+                    Command::new("stats")
+                        .about("Summarize your posts")
+                        .arg(
+                            arg!(-f --format <FORMAT> "Output format (markdown-table pastes into GitHub)")
+                                .value_parser(["plain", "table", "json", "markdown-table"])
+                                .default_value("plain"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("pin")
+                        .about("Pin a post so it shows up in `adot microblog pinned`")
+                        .arg(arg!(<id> "Document id of the post"))
+                        .arg(arg!(--unpin "Remove the pin instead")),
+                )
+                .subcommand(
+                    Command::new("pinned")
+                        .about("List pinned posts in the order they were pinned")
+                        .arg(arg!(--json "Print the posts as JSON")),
+                )
+                .subcommand(
+                    Command::new("list")
+                        .about("List microblog posts")
+                        .arg(
+                            arg!(-f --format <FORMAT> "Output format (ndjson is one JSON object per line)")
+                                .value_parser(["plain", "table", "json", "ndjson"])
+                                .default_value("plain"),
+                        )
+                        .arg(arg!(--fields <FIELDS> "Comma-separated fields to print, e.g. id,time"))
+                        .arg(
+                            arg!(-n --limit <N> "Only list the first N posts in sort order")
+                                .value_parser(clap::value_parser!(u32)),
+                        )
+                        .arg(
+                            arg!(-s --sort <KEY> "Order by time (newest first), length (longest first) or tags (alphabetical)")
+                                .value_parser(["time", "length", "tags"])
+                                .default_value("time"),
+                        )
+                        .arg(arg!(-r --reverse "Reverse the sort order"))
+                        .arg(
+                            arg!(--visibility <VISIBILITY> "Only list posts with this visibility")
+                                .value_parser(VISIBILITIES),
+                        )
+                        .arg(arg!(--"since-id" <ID> "Only list posts created after the post with this id (errors if it doesn't exist). Sorts by time oldest first, so --limit takes the next N posts; --reverse flips those N")),
+                )
+                .subcommand(
+                    Command::new("export")
+                        .about("Export all microblog posts")
+                        .arg(
+                            arg!(-f --format <FORMAT> "Output format (pdf needs the `pdf` feature, --help describes jsonl-firestore)")
+                                .value_parser(["json", "jsonl-firestore", "markdown", "atom", "pdf"])
+                                .default_value("json"),
+                        )
+                        .arg(arg!(-o --out <FILE> "Write the export to a file instead of stdout"))
+                        .arg(
+                            arg!(--visibility <VISIBILITY> "Only export posts with this visibility")
+                                .value_parser(VISIBILITIES),
+                        )
+                        .arg(
+                            arg!(--split <PERIOD> "Write one file per period (2024-06.md, ...) into the --out directory")
+                                .value_parser(["monthly"])
+                                .requires("out"),
+                        )
+                        .after_long_help(
+                            "jsonl-firestore:\n  \
+                             One Firestore REST Document per line, in the shape documents.patch takes:\n  \
+                             {\"name\": \"projects/<project>/databases/(default)/documents/<collection>/<id>\",\n   \
+                             \"fields\": {\"content\": {\"stringValue\": \"...\"}, \"tags\": {\"arrayValue\": {\"values\": [...]}}, ...}}\n  \
+                             Values are typed as stringValue, booleanValue, integerValue (as a string), doubleValue,\n  \
+                             nullValue, arrayValue or mapValue. `gcloud firestore import` cannot read this file, it\n  \
+                             only imports its own managed-export format; replay the lines against the REST API instead.",
+                        ),
+                )
+                .subcommand(
+                    Command::new("reimport-from-csv")
+                        .about("Insert or update posts from a CSV file with id,time,content,tags columns")
+                        .arg(arg!(<file> "CSV file to import; tags are separated by ';'"))
+                        .arg(arg!(--"skip-existing" "Leave posts that already exist untouched instead of overwriting them"))
+                        .arg(arg!(--"dry-run" "Validate the file and report what would change without writing"))
+                        .arg(
+                            arg!(--"max-concurrent-writes" <N> "How many writes may be in flight at once")
+                                .value_parser(clap::value_parser!(u64).range(1..=64))
+                                .default_value("4"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("batch")
+                        .about("Create one post per line of a file, or per record with --separator")
+                        .arg(arg!(<file> "File with one post per line, or '-' to read from stdin"))
+                        .arg(arg!(--separator <LINE> "Split posts on lines containing only LINE (like ---) so posts can span lines"))
+                        .arg(arg!(--raw "Store every post exactly as given, without normalizing whitespace"))
+                        .arg(id_version_arg())
+                        .arg(arg!(--"retry-queue-on-partial-batch" "Write lines that failed to insert to the retry file"))
+                        .arg(
+                            arg!(--"retry-file" <FILE> "Where failed posts are written, in the same format as the input")
+                                .default_value("adot-batch-retry.txt"),
+                        )
+                        .arg(
+                            arg!(--"max-concurrent-writes" <N> "How many writes may be in flight at once")
+                                .value_parser(clap::value_parser!(u64).range(1..=64))
+                                .default_value("4"),
+                        ),
+                ),
         )
         .subcommand(
             Command::new("location")
-                .about("Sends your current location to Firestore"),
+                .about("Sends your current location to Firestore")
+                .args_conflicts_with_subcommands(true)
+                .arg(
+                    arg!(-t --transport <MODE> "How you got here")
+                        .value_parser(["walk", "bike", "car", "train", "plane"]),
+                )
+                .after_long_help(
+                    "History:\n  \
+                     (no flag)                  update 'latest' only\n  \
+                     --history                  update 'latest' and append every check-in to the history\n  \
+                     --history-on-change-only   update 'latest', append only when the place or coordinates\n                             \
+                     differ from the previous 'latest' (also when combined with --history)",
+                )
+                .arg(arg!(--history "Also append this check-in to the location history"))
+                .arg(arg!(--"history-on-change-only" "Only append to the history when the location changed; 'latest' is always updated"))
+                .arg(
+                    arg!(--ip <ADDRESS> "Record where this public IPv4/IPv6 address is instead of this machine")
+                        .value_parser(clap::value_parser!(IpAddr)),
+                )
+                .arg(arg!(--"timezone-from-system" "Store this machine's timezone instead of the one ipinfo guesses"))
+                .arg(arg!(--"store-raw" "Also keep the full ipinfo response in a raw field").conflicts_with("coordinates-only"))
+                .arg(arg!(--"coordinates-only" "Only store coordinates (and timezone), leaving city, region and country empty"))
+                .arg(arg!(--"in-place" "Overwrite 'latest' in one write instead of deleting it first, so it never goes missing"))
+                .subcommand(
+                    Command::new("export")
+                        .about("Export the location history")
+                        .arg(
+                            arg!(-f --format <FORMAT> "Output format (influx is InfluxDB line protocol)")
+                                .value_parser(["json", "influx", "csv"])
+                                .default_value("json"),
+                        )
+                        .arg(arg!(-o --out <FILE> "Write the export to a file instead of stdout"))
+                        .arg(arg!(--gzip "Compress the export with gzip"))
+                        .arg(
+                            arg!(--"min-accuracy" <LEVEL> "Leave out entries coarser than this")
+                                .value_parser(["city", "region", "country"]),
+                        ),
+                )
+                .subcommand(
+                    Command::new("backfill")
+                        .about("Recompute derived fields (like accuracy) on stored locations")
+                        .arg(arg!(--"dry-run" "Report what would change without writing")),
+                )
+                .subcommand(
+                    Command::new("forget")
+                        .about("Delete every history entry for a place (given both, entries must match both)")
+                        .arg(arg!(--city <NAME> "City to forget, case-insensitive"))
+                        .arg(arg!(--country <NAME> "Country code to forget, case-insensitive"))
+                        .group(ArgGroup::new("place").args(["city", "country"]).multiple(true).required(true))
+                        .arg(arg!(--"dry-run" "Show how many entries match without deleting"))
+                        .arg(arg!(-y --yes "Delete without asking for confirmation")),
+                ),
+        )
+        .subcommand(
+            Command::new("rpc")
+                .about("Read newline-delimited JSON commands from stdin and answer on stdout")
+                .long_about(
+                    "Read newline-delimited JSON commands from stdin and answer each with one JSON line on stdout, \
+                     reusing a single Firestore connection for the whole session.\n\n\
+                     Requests: {\"id\": <any, echoed back>, \"cmd\": \"ping\" | \"microblog\" | \"list\", ...}\n  \
+                     ping: no arguments\n  \
+                     microblog: \"content\" (required), \"visibility\", \"tags\", \"raw\", \"id_version\" (4 or 7)\n  \
+                     list: \"limit\"\n\n\
+                     Responses: {\"id\": ..., \"ok\": true, \"result\": ...} or {\"id\": ..., \"ok\": false, \"error\": \"...\"}",
+                ),
+        )
+        .subcommand(
+            Command::new("migrate-timestamps")
+                .about("Rewrite legacy timestamps (RFC2822, epoch seconds or millis) as RFC3339")
+                .arg(arg!(--"dry-run" "Report what would be rewritten without writing")),
+        )
+        .subcommand(
+            Command::new("usage")
+                .about("Count documents and estimate storage per collection")
+                .arg(arg!(--json "Print the totals as JSON")),
+        )
+        .subcommand(
+            Command::new("init")
+                .about("Set adot up: enter and check your settings, write the config and test the connection")
+                .arg(arg!(--"non-interactive" "Take every value from the flags below instead of asking"))
+                .arg(arg!(--"project-id" <ID> "Firebase project id"))
+                .arg(arg!(--credentials <FILE> "Service account key JSON"))
+                .arg(arg!(--"ipinfo-token" <TOKEN> "Token from ipinfo.io"))
+                .arg(arg!(-f --force "Overwrite an existing config file")),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Manage the adot config file")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("init")
+                        .about("Write a commented config template to the default config path")
+                        .arg(arg!(-f --force "Overwrite an existing config file")),
+                )
+                .subcommand(
+                    Command::new("show")
+                        .about("Show the active environment, config file and collection names"),
+                ),
         )
         .subcommand(
             Command::new("readme")
-                .about("Add custom footer to README.md file")
-                .arg(arg!(-c --caption <CAPTION> "Custom caption text (defaults to 'hello world! - month year')")),
+                .about("Add custom footer to README.md (or another markdown file)")
+                .arg(arg!(-c --caption <CAPTION> "Custom caption text (defaults to 'hello world! - month year')"))
+                .arg(arg!(-p --prepend <TEXT> "Text to insert in the footer before the logo"))
+                .arg(
+                    arg!(--"append-footer-to" <FILE> "Markdown file to stamp the footer onto")
+                        .default_value("README.md"),
+                )
+                .arg(arg!(--update "Rewrite the footer if the file already has one"))
+                .arg(arg!(--"dry-run" "Show a diff of the change without writing anything")),
         )
         .get_matches();
 
+    if let Some(adot_env) = matches.get_one::<String>("env") {
+        unsafe {
+            std::env::set_var("ADOT_ENV", adot_env);
+        }
+    }
+
+    if let Some(seconds) = matches.get_one::<u64>("firestore-timeout") {
+        unsafe {
+            std::env::set_var("ADOT_FIRESTORE_TIMEOUT", seconds.to_string());
+        }
+    }
+    if matches.get_flag("verbose") {
+        VERBOSE.store(true, Ordering::SeqCst);
+    }
+    if matches.get_flag("no-buffer") {
+        UNBUFFERED.store(true, Ordering::SeqCst);
+    }
+    if matches.get_flag("no-deprecation-warnings") {
+        DEPRECATIONS_SILENCED.store(true, Ordering::SeqCst);
+    }
+
     if let Some(sub_matches) = matches.subcommand_matches("microblog") {
-        handle_microblog(sub_matches).await?;
-    } else if matches.subcommand_matches("location").is_some() {
-        handle_location().await?;
+        match sub_matches.subcommand() {
+            Some(("replace", replace_matches)) => handle_microblog_replace(replace_matches).await?,
+            Some(("amend", amend_matches)) => handle_microblog_amend(amend_matches).await?,
+            Some(("continue", continue_matches)) => {
+                handle_microblog(continue_matches, true).await?
+            }
+            Some(("stats", stats_matches)) => handle_microblog_stats(stats_matches).await?,
+            Some(("pin", pin_matches)) => handle_microblog_pin(pin_matches).await?,
+            Some(("pinned", pinned_matches)) => handle_microblog_pinned(pinned_matches).await?,
+            Some(("list", list_matches)) => handle_microblog_list(list_matches).await?,
+            Some(("export", export_matches)) => handle_microblog_export(export_matches).await?,
+            Some(("reimport-from-csv", import_matches)) => {
+                handle_microblog_reimport(import_matches).await?
+            }
+            Some(("batch", batch_matches)) => handle_microblog_batch(batch_matches).await?,
+            _ => handle_microblog(sub_matches, false).await?,
+        }
+    } else if let Some(sub_matches) = matches.subcommand_matches("location") {
+        match sub_matches.subcommand() {
+            Some(("export", export_matches)) => handle_location_export(export_matches).await?,
+            Some(("backfill", backfill_matches)) => {
+                handle_location_backfill(backfill_matches).await?
+            }
+            Some(("forget", forget_matches)) => handle_location_forget(forget_matches).await?,
+            _ => handle_location(sub_matches).await?,
+        }
+    } else if matches.subcommand_matches("rpc").is_some() {
+        handle_rpc().await?;
+    } else if let Some(sub_matches) = matches.subcommand_matches("migrate-timestamps") {
+        handle_migrate_timestamps(sub_matches).await?;
+    } else if let Some(sub_matches) = matches.subcommand_matches("usage") {
+        handle_usage(sub_matches).await?;
+    } else if let Some(sub_matches) = matches.subcommand_matches("init") {
+        handle_init(sub_matches).await?;
+    } else if let Some(sub_matches) = matches.subcommand_matches("config") {
+        match sub_matches.subcommand() {
+            Some(("init", init_matches)) => handle_config_init(init_matches)?,
+            Some(("show", _)) => handle_config_show()?,
+            _ => {}
+        }
     } else if let Some(sub_matches) = matches.subcommand_matches("readme") {
         handle_readme(sub_matches)?;
     } else {
-        println!("No valid subcommand provided. Use `adot microblog 'your content'`, `adot location`, or `adot readme`.");
+        println!("No valid subcommand provided. Use `adot microblog 'your content'`, `adot location`, `adot usage`, `adot init`, or `adot readme`.");
     }
     Ok(())
 }
 
-async fn handle_microblog(
-    matches: &ArgMatches,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let content = matches.get_one::<String>("content").unwrap();
+/// Set on Ctrl-C while a long-running command is going. Those commands check it between documents
+/// so the write in flight always finishes before they stop.
+static CANCELLED: AtomicBool = AtomicBool::new(false);
 
-    let id = Uuid::new_v4().to_string();
-    let timestamp = Utc::now().to_rfc3339();
+/// Installs the Ctrl-C handler for a long-running command. A second Ctrl-C quits immediately.
+fn watch_for_ctrl_c() {
+    tokio::spawn(async {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if CANCELLED.swap(true, Ordering::SeqCst) {
+                std::process::exit(130);
+            }
+            eprintln!("\n⏹️  Stopping after the current document, press Ctrl-C again to quit now");
+        }
+    });
+}
 
-    let project_id = env::var("PROJECT_ID").map_err(|e| format!("PROJECT_ID not found: {}", e))?;
-    let google_credentials = env::var("GOOGLE_APPLICATION_CREDENTIALS")
-        .map_err(|e| format!("GOOGLE_APPLICATION_CREDENTIALS not found: {}", e))?;
+fn cancelled() -> bool {
+    CANCELLED.load(Ordering::SeqCst)
+}
 
-    unsafe {
-        std::env::set_var("GOOGLE_APPLICATION_CREDENTIALS", google_credentials);
-    }
+/// Deprecated flags and behaviors, each with what to use instead.
+const DEPRECATIONS: [(&str, &str); 1] = [("deleting 'latest' before each check-in", "--in-place")];
 
-    let db = FirestoreDb::new(&project_id).await?;
-    const COLLECTION_NAME: &str = "microblog";
+static DEPRECATIONS_SILENCED: AtomicBool = AtomicBool::new(false);
 
-    let microblog_struct = MicroblogStruct {
-        id,
-        content: content.to_string(),
-        time: timestamp,
+/// Set by `--verbose`.
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// Set by `--no-buffer`. Record output is buffered by default, which is much faster into a pipe;
+/// unbuffered output shows every line as soon as it is printed.
+static UNBUFFERED: AtomicBool = AtomicBool::new(false);
+
+/// Warns on stderr that `item` is deprecated, once per run. `item` must be in `DEPRECATIONS`.
+fn deprecated(item: &'static str) {
+    static WARNED: Mutex<BTreeSet<&str>> = Mutex::new(BTreeSet::new());
+
+    if DEPRECATIONS_SILENCED.load(Ordering::SeqCst) {
+        return;
+    }
+    let Some((_, alternative)) = DEPRECATIONS.iter().find(|(name, _)| *name == item) else {
+        return;
     };
+    if WARNED.lock().unwrap().insert(item) {
+        eprintln!("warning: {} is deprecated, use {}", item, alternative);
+    }
+}
 
-    let object_returned: MicroblogStruct = db
-        .fluent()
-        .insert()
-        .into(COLLECTION_NAME)
-        .document_id(&microblog_struct.id)
-        .object(&microblog_struct)
-        .execute()
-        .await?;
+/// The active environment, taken from `--env` or `ADOT_ENV` and defaulting to `prod`.
+fn active_env() -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let adot_env = env::var("ADOT_ENV").unwrap_or_else(|_| "prod".to_string());
+    if adot_env.is_empty()
+        || !adot_env
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "Invalid environment '{}': use letters, digits, '-' or '_'",
+            adot_env
+        )
+        .into());
+    }
+    Ok(adot_env)
+}
 
-    println!("Inserted: {:?}", object_returned);
+/// Reads a setting from its environment variable, falling back to the active profile and then the
+/// top level of the config file.
+fn setting<F>(key: &str, pick: F) -> Result<String, Box<dyn std::error::Error + Send + Sync>>
+where
+    F: Fn(&config::Settings) -> Option<&String>,
+{
+    if let Ok(value) = env::var(key) {
+        return Ok(value);
+    }
 
-    Ok(())
+    config::get()?.lookup(&active_env()?, pick).ok_or_else(|| {
+        format!(
+            "{} not found: set it in the environment or in {}",
+            key,
+            config::path().display()
+        )
+        .into()
+    })
 }
 
-async fn handle_location() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let timestamp = Utc::now().to_rfc3339();
+/// Resolves a collection name for the active environment. Names come from the config file, and an
+/// environment other than `prod` without its own profile gets its name as a prefix
+/// (`dev_microblog`) so it never touches prod data.
+fn collection(base: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let adot_env = active_env()?;
+    let config = config::get()?;
 
-    let project_id = env::var("PROJECT_ID").map_err(|e| format!("PROJECT_ID not found: {}", e))?;
-    let google_credentials = env::var("GOOGLE_APPLICATION_CREDENTIALS")
-        .map_err(|e| format!("GOOGLE_APPLICATION_CREDENTIALS not found: {}", e))?;
-    let ipinfo_token =
-        env::var("IPINFO_TOKEN").map_err(|e| format!("IPINFO_TOKEN not found: {}", e))?;
+    let name = config
+        .lookup(&adot_env, |settings| match base {
+            "microblog" => settings.collections.microblog.as_ref(),
+            "location" => settings.collections.location.as_ref(),
+            "location_history" => settings.collections.location_history.as_ref(),
+            _ => None,
+        })
+        .unwrap_or_else(|| base.to_string());
+
+    if adot_env == "prod" || config.profiles.contains_key(&adot_env) {
+        Ok(name)
+    } else {
+        Ok(format!("{}_{}", adot_env, name))
+    }
+}
+
+async fn connect_firestore() -> Result<FirestoreDb, Box<dyn std::error::Error + Send + Sync>> {
+    let adot_env = active_env()?;
+    if VERBOSE.load(Ordering::SeqCst) {
+        eprintln!("🌐 Environment: {}", adot_env);
+    }
+
+    let project_id = setting("PROJECT_ID", |settings| settings.project_id.as_ref())?;
+    let google_credentials = setting("GOOGLE_APPLICATION_CREDENTIALS", |settings| {
+        settings.google_application_credentials.as_ref()
+    })?;
 
     unsafe {
         std::env::set_var("GOOGLE_APPLICATION_CREDENTIALS", google_credentials);
     }
 
-    let db = FirestoreDb::new(&project_id).await?;
-    const COLLECTION_NAME: &str = "location";
+    let read_retries = retry_budget(|retries| retries.read.as_ref(), 3);
+    let options = FirestoreDbOptions::new(project_id).with_max_retries(read_retries);
+    Ok(FirestoreDb::with_options(options).await?)
+}
+
+fn retry_budget<F>(pick: F, default: usize) -> usize
+where
+    F: Fn(&config::Retries) -> Option<&usize>,
+{
+    match (config::get(), active_env()) {
+        (Ok(config), Ok(adot_env)) => config
+            .lookup(&adot_env, |settings| pick(&settings.retries))
+            .unwrap_or(default),
+        _ => default,
+    }
+}
+
+/// Failures worth retrying: the database saying so, or the network dropping.
+fn is_transient(error: &FirestoreError) -> bool {
+    match error {
+        FirestoreError::DatabaseError(db_err) => db_err.retry_possible,
+        FirestoreError::NetworkError(_) => true,
+        _ => false,
+    }
+}
 
-    println!("🗑️  Cleaning up existing location entry...");
-    if let Ok(_) = db
-        .fluent()
-        .delete()
-        .from(COLLECTION_NAME)
-        .document_id("latest")
-        .execute()
-        .await
+/// How long one write attempt may take, from `--firestore-timeout` (via `ADOT_FIRESTORE_TIMEOUT`)
+/// or `firestore_timeout` in the config. Unset means no limit.
+fn firestore_timeout() -> Option<Duration> {
+    let seconds = match env::var("ADOT_FIRESTORE_TIMEOUT") {
+        Ok(value) => value.parse().ok(),
+        Err(_) => match (config::get(), active_env()) {
+            (Ok(config), Ok(adot_env)) => {
+                config.lookup(&adot_env, |settings| settings.firestore_timeout.as_ref())
+            }
+            _ => None,
+        },
+    };
+    seconds.map(Duration::from_secs)
+}
+
+/// Runs a Firestore write, retrying transient failures and timeouts up to the `retries.write`
+/// budget. Reads are retried by the Firestore client itself using `retries.read`.
+async fn retry_write<T, F, Fut>(mut write: F) -> Result<T, Box<dyn std::error::Error + Send + Sync>>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = FirestoreResult<T>>,
+{
+    let budget = retry_budget(|retries| retries.write.as_ref(), 0);
+    let timeout = firestore_timeout();
+    let mut attempt = 0;
+
+    loop {
+        let outcome = match timeout {
+            Some(limit) => tokio::time::timeout(limit, write()).await.ok(),
+            None => Some(write().await),
+        };
+        match outcome {
+            None if attempt < budget => {
+                attempt += 1;
+                eprintln!("⏳ Write timed out, retrying {}/{}", attempt, budget);
+            }
+            None => {
+                return Err(format!(
+                    "Firestore write timed out after {}s (it may still have been applied)",
+                    timeout.unwrap_or_default().as_secs()
+                )
+                .into())
+            }
+            Some(Err(e)) if attempt < budget && is_transient(&e) => {
+                attempt += 1;
+                eprintln!("⏳ Write failed ({}), retrying {}/{}", e, attempt, budget);
+                tokio::time::sleep(Duration::from_millis(500 << attempt)).await;
+            }
+            Some(result) => return Ok(result?),
+        }
+    }
+}
+
+/// Runs a new post through the WASM plugin from the config, if one is set. A plugin that fails,
+/// or tries to change the post's id or time, rejects the post rather than letting it through
+/// untransformed.
+fn apply_plugin(
+    post: MicroblogStruct,
+) -> Result<MicroblogStruct, Box<dyn std::error::Error + Send + Sync>> {
+    let Some(path) = config::get()?.lookup(&active_env()?, |settings| settings.plugin.as_ref())
+    else {
+        return Ok(post);
+    };
+
+    #[cfg(not(feature = "wasm"))]
+    {
+        Err(format!(
+            "A plugin ({}) is configured but adot was built without the `wasm` feature, reinstall with `--features wasm`",
+            path
+        )
+        .into())
+    }
+    #[cfg(feature = "wasm")]
     {
-        println!("Deleted existing 'latest' entry");
+        let transformed = plugin::transform(&path, &post)?;
+        if transformed.id != post.id || transformed.time != post.time {
+            return Err("Plugin must not change a post's id or time".into());
+        }
+        if !VISIBILITIES.contains(&transformed.visibility.as_str()) {
+            return Err(format!(
+                "Plugin returned invalid visibility '{}'",
+                transformed.visibility
+            )
+            .into());
+        }
+        Ok(transformed)
     }
+}
 
-    println!("📍 Fetching location data from ipinfo.io...");
-    let url = format!("https://ipinfo.io/json?token={}", ipinfo_token);
+/// Converts CRLF (and stray CR) line endings to LF, strips trailing whitespace from every line and
+/// trims blank lines from both ends.
+fn normalize_content(content: &str) -> String {
+    content
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim_matches('\n')
+        .to_string()
+}
 
-    let response = reqwest::get(&url).await?;
-    let status = response.status();
+/// Collects `#word` tokens that start a word as lowercase tags, in order and without repeats.
+/// With `strip`, the tokens and the space before them are removed from the content.
+fn extract_hashtags(content: &str, strip: bool) -> (String, Vec<String>) {
+    let mut tags: Vec<String> = Vec::new();
+    let mut kept = String::with_capacity(content.len());
+    let mut chars = content.char_indices().peekable();
+    let mut previous: Option<char> = None;
 
-    if !status.is_success() {
-        let error_text = response.text().await?;
-        return Err(format!("API request failed with status {}: {}", status, error_text).into());
+    while let Some((start, c)) = chars.next() {
+        if c == '#' && previous.is_none_or(char::is_whitespace) {
+            let mut end = start + 1;
+            while let Some(&(index, next)) = chars
+                .peek()
+                .filter(|(_, next)| next.is_alphanumeric() || *next == '_' || *next == '-')
+            {
+                end = index + next.len_utf8();
+                chars.next();
+            }
+            let tag = content[start + 1..end].trim_end_matches('-').to_lowercase();
+            if !tag.is_empty() {
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+                if strip {
+                    if kept.ends_with(' ') {
+                        kept.pop();
+                    } else if chars.peek().is_some_and(|(_, next)| *next == ' ') {
+                        chars.next();
+                    }
+                } else {
+                    kept.push_str(&content[start..end]);
+                }
+                previous = kept.chars().last();
+                continue;
+            }
+        }
+        kept.push(c);
+        previous = Some(c);
     }
 
-    let location_data: serde_json::Value = response.json().await?;
-    let location_struct = LocationStruct {
-        city: location_data["city"]
-            .as_str()
-            .ok_or("Missing city field")?
-            .to_string(),
-        region: location_data["region"]
-            .as_str()
-            .ok_or("Missing region field")?
-            .to_string(),
-        country: location_data["country"]
-            .as_str()
-            .ok_or("Missing country field")?
-            .to_string(),
-        timezone: location_data["timezone"]
-            .as_str()
-            .ok_or("Missing timezone field")?
-            .to_string(),
-        time: TimeStruct { utc: timestamp },
-    };
-
-    let object_returned: LocationStruct = db
-        .fluent()
-        .insert()
-        .into(COLLECTION_NAME)
-        .document_id("latest")
-        .object(&location_struct)
-        .execute()
-        .await?;
+    if strip {
+        kept = normalize_content(&kept);
+    }
+    (kept, tags)
+}
+
+fn confirm(prompt: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    print!("{} [y/N] ", prompt);
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Whether length checks only warn. It is meant for pipelines that must never fail on content
+/// size, so it wins over anything that makes validation stricter.
+fn length_warning_only(
+    matches: &ArgMatches,
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    if matches.get_flag("content-length-warning-only") {
+        return Ok(true);
+    }
+    Ok(config::get()?
+        .lookup(&active_env()?, |settings| {
+            settings.content_length_warning_only.as_ref()
+        })
+        .unwrap_or(false))
+}
+
+/// Creates a post. With `continuing`, it replies to the most recent post.
+async fn handle_microblog(
+    matches: &ArgMatches,
+    continuing: bool,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let content = matches.get_one::<String>("content").unwrap();
+    let content = if matches.get_flag("raw") {
+        content.to_string()
+    } else {
+        let normalized = normalize_content(content);
+        if normalized.len() != content.len() {
+            println!(
+                "🧹 Normalized content ({} → {} bytes)",
+                content.len(),
+                normalized.len()
+            );
+        }
+        normalized
+    };
+    let (content, tags) = if matches.get_flag("extract-hashtags") {
+        let strip = matches.get_flag("strip-hashtags")
+            || config::get()?
+                .lookup(&active_env()?, |settings| settings.strip_hashtags.as_ref())
+                .unwrap_or(false);
+        let (content, tags) = extract_hashtags(&content, strip);
+        if !tags.is_empty() {
+            println!("🏷️  Tags: {}", tags.join(", "));
+        }
+        (content, tags)
+    } else {
+        (content, Vec::new())
+    };
+    if content.is_empty() {
+        return Err("Microblog content is empty".into());
+    }
+    if let Some(max_bytes) = matches.get_one::<usize>("max-bytes") {
+        if content.len() > *max_bytes {
+            let message = format!(
+                "Content is {} bytes of UTF-8 ({} characters), over the {} byte limit",
+                content.len(),
+                content.chars().count(),
+                max_bytes
+            );
+            if !length_warning_only(matches)? {
+                return Err(message.into());
+            }
+            eprintln!("⚠️  {}, posting anyway", message);
+        }
+    }
+
+    let id = new_post_id(matches.get_one::<String>("id-version").unwrap());
+    let timestamp = Utc::now().to_rfc3339();
+    let visibility = if matches.get_flag("private") {
+        "private".to_string()
+    } else {
+        matches.get_one::<String>("visibility").unwrap().to_string()
+    };
+
+    let db = connect_firestore().await?;
+    let collection_name = collection("microblog")?;
+
+    let reply_to = if continuing {
+        let latest: Vec<MicroblogStruct> = db
+            .fluent()
+            .select()
+            .from(collection_name.as_str())
+            .order_by([(
+                path!(MicroblogStruct::time),
+                FirestoreQueryDirection::Descending,
+            )])
+            .limit(1)
+            .obj()
+            .query()
+            .await?;
+        let latest = latest
+            .into_iter()
+            .next()
+            .ok_or("There are no posts yet to continue from")?;
+        println!("🧵 Continuing {}", latest.id);
+        Some(latest.id)
+    } else {
+        None
+    };
+
+    // The plugin sees the plaintext, so it runs before encryption.
+    let mut microblog_struct = apply_plugin(MicroblogStruct {
+        id,
+        content,
+        time: timestamp,
+        tags,
+        visibility,
+        encrypted: false,
+        pinned_at: None,
+        location: None,
+        reply_to,
+    })?;
+    if matches.get_flag("encrypt") {
+        microblog_struct.content =
+            crypto::encrypt(&microblog_struct.content, &crypto::passphrase(true)?)?;
+        microblog_struct.encrypted = true;
+    }
+
+    let slack = matches.get_flag("slack");
+    #[cfg(not(feature = "slack"))]
+    if slack {
+        return Err(
+            "adot was built without the `slack` feature, reinstall with `--features slack`".into(),
+        );
+    }
+    #[cfg(feature = "slack")]
+    let slack_webhook_url = if slack {
+        Some(setting("SLACK_WEBHOOK_URL", |settings| {
+            settings.slack_webhook_url.as_ref()
+        })?)
+    } else {
+        None
+    };
+
+    if matches.get_flag("with-location") {
+        microblog_struct.location = post_location(&microblog_struct.time).await;
+    }
+
+    let object_returned: MicroblogStruct = retry_write(|| {
+        db.fluent()
+            .insert()
+            .into(&collection_name)
+            .document_id(&microblog_struct.id)
+            .object(&microblog_struct)
+            .execute()
+    })
+    .await?;
+
+    println!("Inserted: {:?}", object_returned);
+
+    #[cfg(feature = "slack")]
+    if let Some(webhook_url) = slack_webhook_url {
+        if object_returned.visibility == "public" {
+            slack::post(&webhook_url, &object_returned).await?;
+            println!("💬 Mirrored to Slack");
+        } else {
+            println!(
+                "⚠️  Not mirroring a {} post to Slack",
+                object_returned.visibility
+            );
+        }
+    }
 
-    println!("✅ Updated location: {:?}", object_returned);
     Ok(())
 }
 
-fn handle_readme(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let current_dir = env::current_dir()?;
-    let readme_path = current_dir.join("README.md");
-    let akshithio_dir = current_dir.join("akshithio");
-    let month = Utc::now().format("%b").to_string().to_lowercase();
-    let year = Utc::now().format("%Y");
+async fn handle_microblog_replace(
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let id = matches.get_one::<String>("id").unwrap();
+    let json = matches.get_one::<String>("json").unwrap();
+    let from_stdin = json == "-";
 
-    let caption = if let Some(custom_caption) = matches.get_one::<String>("caption") {
-        format!("{} - {}", custom_caption, format!("{} {}", month, year))
+    let raw = if from_stdin {
+        let mut buffer = String::new();
+        io::stdin().read_to_string(&mut buffer)?;
+        buffer
     } else {
-        format!("hello world! - {} {}", month, year)
+        json.to_string()
     };
 
-    if !akshithio_dir.exists() {
-        fs::create_dir_all(&akshithio_dir)?;
-        println!("📁 Created akshithio directory");
+    let mut document: serde_json::Value =
+        serde_json::from_str(&raw).map_err(|e| format!("Replacement is not valid JSON: {}", e))?;
+    let object = document
+        .as_object_mut()
+        .ok_or("Replacement must be a JSON object")?;
+    // A misspelled key or a missing visibility would otherwise fall back to the default and
+    // silently clear tags or make a private post public.
+    let unknown: Vec<&str> = object
+        .keys()
+        .map(String::as_str)
+        .filter(|key| !MICROBLOG_FIELDS.contains(key))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(format!(
+            "Unknown field(s) {}, expected some of: {}",
+            unknown.join(", "),
+            MICROBLOG_FIELDS.join(", ")
+        )
+        .into());
+    }
+    if !object.contains_key("visibility") {
+        return Err("Replacement must set visibility explicitly".into());
+    }
+    // The id always comes from the command line, so the JSON may leave it out.
+    if let Some(json_id) = object
+        .insert("id".to_string(), id.clone().into())
+        .filter(|json_id| json_id != id.as_str())
+    {
+        println!(
+            "⚠️  Ignoring id {} from the JSON, keeping \"{}\"",
+            json_id, id
+        );
+    }
+    let replacement: MicroblogStruct = serde_json::from_value(document)
+        .map_err(|e| format!("Replacement is not a valid microblog document: {}", e))?;
+    if !VISIBILITIES.contains(&replacement.visibility.as_str()) {
+        return Err(format!(
+            "Invalid visibility '{}', expected one of: {}",
+            replacement.visibility,
+            VISIBILITIES.join(", ")
+        )
+        .into());
+    }
 
-        let adot_akshithio_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("akshithio");
+    let db = connect_firestore().await?;
+    let collection_name = collection("microblog")?;
 
-        if adot_akshithio_path.exists() {
-            let light_logo_src = adot_akshithio_path.join("light-logo.png");
-            let dark_logo_src = adot_akshithio_path.join("dark-logo.png");
-            let light_logo_dst = akshithio_dir.join("light-logo.png");
-            let dark_logo_dst = akshithio_dir.join("dark-logo.png");
+    let existing: MicroblogStruct = db
+        .fluent()
+        .select()
+        .by_id_in(&collection_name)
+        .obj()
+        .one(id)
+        .await?
+        .ok_or_else(|| format!("No microblog post found with id '{}'", id))?;
 
-            if light_logo_src.exists() {
-                fs::copy(&light_logo_src, &light_logo_dst)?;
-                println!("📄 Copied light-logo.png");
-            }
+    let old_value = serde_json::to_value(&existing)?;
+    let new_value = serde_json::to_value(&replacement)?;
+    if !print_document_diff(&old_value, &new_value) {
+        println!("⚠️  Replacement is identical to the stored post, nothing to do");
+        return Ok(());
+    }
 
-            if dark_logo_src.exists() {
-                fs::copy(&dark_logo_src, &dark_logo_dst)?;
-                println!("📄 Copied dark-logo.png");
-            }
+    if !matches.get_flag("yes") {
+        if from_stdin {
+            return Err(
+                "Cannot ask for confirmation while reading JSON from stdin, pass --yes".into(),
+            );
+        }
+        if !confirm("Replace this post?")? {
+            println!("❌ Replacement cancelled");
+            return Ok(());
         }
     }
 
-    let footer = format!("\n<br />\n\n&nbsp;<img src=\"./akshithio/light-logo.png#gh-dark-mode-only\" alt=\"Akshith Garapati's Personal Icon - Doodle of Two Eyes Dark Mode\" width =\"24px\" align = \"left\" /><img src=\"./akshithio/dark-logo.png#gh-light-mode-only\" alt=\"Akshith Garapati's Personal Icon - Doodle of Two Eyes Dark Mode\" width =\"24px\" align = \"left\" /> {} ", caption);
+    let object_returned: MicroblogStruct = retry_write(|| {
+        db.fluent()
+            .update()
+            .in_col(&collection_name)
+            .precondition(FirestoreWritePrecondition::Exists(true))
+            .document_id(id)
+            .object(&replacement)
+            .execute()
+    })
+    .await?;
 
-    if readme_path.exists() {
-        let mut content = fs::read_to_string(&readme_path)?;
+    println!("✅ Replaced: {:?}", object_returned);
+    Ok(())
+}
 
-        if !content.contains("./akshithio/light-logo.png") {
-            content = content.trim_end().to_string();
-            content.push_str(&footer);
-            fs::write(&readme_path, content)?;
-            println!("✅ Added footer to existing README.md");
-        } else {
-            println!("⚠️  Footer already exists in README.md");
+async fn handle_microblog_amend(
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let id = matches.get_one::<String>("id").unwrap();
+    let db = connect_firestore().await?;
+    let collection_name = collection("microblog")?;
+
+    let existing: MicroblogStruct = db
+        .fluent()
+        .select()
+        .by_id_in(&collection_name)
+        .obj()
+        .one(id)
+        .await?
+        .ok_or_else(|| format!("No microblog post found with id '{}'", id))?;
+
+    let mut amended = existing.clone();
+    let mut fields = Vec::new();
+    if let Some(content) = matches.get_one::<String>("content") {
+        let content = normalize_content(content);
+        if content.is_empty() {
+            return Err("Microblog content is empty".into());
         }
+        amended.content = content;
+        fields.push(path!(MicroblogStruct::content));
+    }
+    if let Some(tags) = matches.get_one::<String>("tags") {
+        amended.tags = tags
+            .split(',')
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        fields.push(path!(MicroblogStruct::tags));
+    }
+
+    // Compare plaintext, then encrypt just before writing.
+    let mut shown = existing.clone();
+    let passphrase = if existing.encrypted && matches.contains_id("content") {
+        let passphrase = crypto::passphrase(false)?;
+        shown.content = crypto::decrypt(&existing.content, &passphrase)?;
+        Some(passphrase)
     } else {
-        let content = format!("# README{}", footer);
-        fs::write(&readme_path, content)?;
-        println!("✅ Created new README.md with footer");
+        None
+    };
+    if !print_document_diff(
+        &serde_json::to_value(&shown)?,
+        &serde_json::to_value(&amended)?,
+    ) {
+        println!("⚠️  Nothing to amend, the post already looks like that");
+        return Ok(());
+    }
+    if let Some(passphrase) = passphrase {
+        amended.content = crypto::encrypt(&amended.content, &passphrase)?;
     }
 
+    let _: MicroblogStruct = retry_write(|| {
+        db.fluent()
+            .update()
+            .fields(&fields)
+            .in_col(&collection_name)
+            .precondition(FirestoreWritePrecondition::Exists(true))
+            .document_id(id)
+            .object(&amended)
+            .execute()
+    })
+    .await?;
+
+    println!("✅ Amended {}", id);
     Ok(())
 }
+
+async fn fetch_microblogs(
+    db: &FirestoreDb,
+) -> Result<Vec<MicroblogStruct>, Box<dyn std::error::Error + Send + Sync>> {
+    let collection_name = collection("microblog")?;
+
+    let posts: Vec<MicroblogStruct> = db
+        .fluent()
+        .select()
+        .from(collection_name.as_str())
+        .order_by([(
+            path!(MicroblogStruct::time),
+            FirestoreQueryDirection::Ascending,
+        )])
+        .obj()
+        .query()
+        .await?;
+
+    Ok(posts)
+}
+
+async fn handle_microblog_list(
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let format = matches.get_one::<String>("format").unwrap();
+    let fields = parse_fields(
+        matches.get_one::<String>("fields").map(String::as_str),
+        &MICROBLOG_FIELDS,
+        &["time", "content"],
+    )?;
+
+    let db = connect_firestore().await?;
+    let collection_name = collection("microblog")?;
+
+    let sort = matches.get_one::<String>("sort").unwrap();
+    let reverse = matches.get_flag("reverse");
+    let limit = matches.get_one::<u32>("limit").copied();
+    let visibility = matches.get_one::<String>("visibility");
+    // Filtering on visibility in the query would need a composite index with time, so it is done
+    // here and the limit has to wait until after it.
+    let limit_in_query = sort == "time" && visibility.is_none();
+
+    // Time ordering (and the limit that goes with it) is left to Firestore, the other keys need
+    // every post client-side before they can be sorted. With --since-id the limit must take the
+    // posts right after the reference one, or anything between it and the newest N is skipped.
+    let since_id = matches.contains_id("since-id");
+    let direction = if reverse || since_id {
+        FirestoreQueryDirection::Ascending
+    } else {
+        FirestoreQueryDirection::Descending
+    };
+    // Posts sharing the reference post's exact time are treated as already seen.
+    let since_time = match matches.get_one::<String>("since-id") {
+        Some(since_id) => {
+            let reference: MicroblogStruct = db
+                .fluent()
+                .select()
+                .by_id_in(&collection_name)
+                .obj()
+                .one(since_id)
+                .await?
+                .ok_or_else(|| format!("No microblog post found with id '{}'", since_id))?;
+            Some(reference.time)
+        }
+        None => None,
+    };
+
+    let mut query = db
+        .fluent()
+        .select()
+        .from(collection_name.as_str())
+        .order_by([(path!(MicroblogStruct::time), direction)]);
+    if let Some(since_time) = since_time {
+        query = query.filter(|q| {
+            q.for_all([q
+                .field(path!(MicroblogStruct::time))
+                .greater_than(since_time.clone())])
+        });
+    }
+    if let (Some(limit), true) = (limit, limit_in_query) {
+        query = query.limit(limit);
+    }
+    let mut posts: Vec<MicroblogStruct> = query.obj().query().await?;
+    if let Some(visibility) = visibility {
+        posts.retain(|post| post.visibility == *visibility);
+    }
+    // Before sorting, so encrypted posts are ordered by their text rather than their ciphertext.
+    decrypt_posts(&mut posts)?;
+
+    if sort != "time" {
+        match sort.as_str() {
+            "length" => posts.sort_by_key(|post| std::cmp::Reverse(post.content.chars().count())),
+            _ => posts.sort_by_key(|post| (post.tags.is_empty(), post.tags.join(","))),
+        }
+        if reverse {
+            posts.reverse();
+        }
+    }
+    if let (Some(limit), false) = (limit, limit_in_query) {
+        posts.truncate(limit as usize);
+    }
+    if sort == "time" && since_id && reverse {
+        posts.reverse();
+    }
+
+    let records = posts
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<_>, _>>()?;
+    print_records(&records, &fields, format)
+}
+
+/// Decrypts encrypted posts for display, asking for the passphrase only if there are any.
+fn decrypt_posts(
+    posts: &mut [MicroblogStruct],
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if posts.iter().any(|post| post.encrypted) {
+        let passphrase = crypto::passphrase(false)?;
+        for post in posts.iter_mut().filter(|post| post.encrypted) {
+            post.content = crypto::decrypt(&post.content, &passphrase)
+                .unwrap_or_else(|e| format!("🔒 could not decrypt: {}", e));
+        }
+    }
+    Ok(())
+}
+
+async fn handle_microblog_stats(
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let db = connect_firestore().await?;
+    let posts = fetch_microblogs(&db).await?;
+
+    let count =
+        |keep: &dyn Fn(&MicroblogStruct) -> bool| posts.iter().filter(|post| keep(post)).count();
+    let mut tag_counts: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut month_counts: BTreeMap<String, usize> = BTreeMap::new();
+    for post in &posts {
+        for tag in &post.tags {
+            *tag_counts.entry(tag).or_default() += 1;
+        }
+        if let Some(time) = parse_timestamp(&post.time) {
+            *month_counts
+                .entry(time.format("%Y-%m").to_string())
+                .or_default() += 1;
+        }
+    }
+    // Ties go to the earliest tag or month.
+    let top_tag = tag_counts
+        .iter()
+        .rev()
+        .max_by_key(|(_, count)| **count)
+        .map(|(tag, count)| format!("{} ({})", tag, count));
+    let busiest_month = month_counts
+        .iter()
+        .rev()
+        .max_by_key(|(_, count)| **count)
+        .map(|(month, count)| format!("{} ({})", month, count));
+
+    // Ciphertext length says nothing about the post, so encrypted posts are left out.
+    let readable: Vec<usize> = posts
+        .iter()
+        .filter(|post| !post.encrypted)
+        .map(|post| post.content.chars().count())
+        .collect();
+    let average_length = if readable.is_empty() {
+        0
+    } else {
+        readable.iter().sum::<usize>() / readable.len()
+    };
+    let day = |post: Option<&MicroblogStruct>| {
+        post.and_then(|post| parse_timestamp(&post.time))
+            .map(|time| time.format("%Y-%m-%d").to_string())
+    };
+
+    let stats: Vec<(&str, serde_json::Value)> = vec![
+        ("posts", posts.len().into()),
+        ("public", count(&|post| post.visibility == "public").into()),
+        (
+            "unlisted",
+            count(&|post| post.visibility == "unlisted").into(),
+        ),
+        (
+            "private",
+            count(&|post| post.visibility == "private").into(),
+        ),
+        ("encrypted", count(&|post| post.encrypted).into()),
+        ("pinned", count(&|post| post.pinned_at.is_some()).into()),
+        ("tagged", count(&|post| !post.tags.is_empty()).into()),
+        ("distinct_tags", tag_counts.len().into()),
+        ("top_tag", top_tag.into()),
+        ("first_post", day(posts.first()).into()),
+        ("last_post", day(posts.last()).into()),
+        ("busiest_month", busiest_month.into()),
+        ("average_length", average_length.into()),
+    ];
+
+    let format = matches.get_one::<String>("format").unwrap();
+    if format == "json" {
+        let object: serde_json::Map<String, serde_json::Value> = stats
+            .into_iter()
+            .map(|(stat, value)| (stat.to_string(), value))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&object)?);
+        return Ok(());
+    }
+
+    let records: Vec<serde_json::Value> = stats
+        .into_iter()
+        .map(|(stat, value)| serde_json::json!({ "stat": stat, "value": value }))
+        .collect();
+    let fields = ["stat", "value"].map(String::from);
+    print_records(&records, &fields, format)
+}
+
+async fn handle_microblog_pin(
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let id = matches.get_one::<String>("id").unwrap();
+    let db = connect_firestore().await?;
+    let collection_name = collection("microblog")?;
+
+    let mut post: MicroblogStruct = db
+        .fluent()
+        .select()
+        .by_id_in(&collection_name)
+        .obj()
+        .one(id)
+        .await?
+        .ok_or_else(|| format!("No post with id {}", id))?;
+
+    let unpin = matches.get_flag("unpin");
+    if post.pinned_at.is_some() != unpin {
+        println!(
+            "⚠️  Post {} is already {}",
+            id,
+            if unpin { "unpinned" } else { "pinned" }
+        );
+        return Ok(());
+    }
+    post.pinned_at = if unpin {
+        None
+    } else {
+        Some(Utc::now().to_rfc3339())
+    };
+
+    let _: MicroblogStruct = retry_write(|| {
+        db.fluent()
+            .update()
+            .fields(paths!(MicroblogStruct::{pinned_at}))
+            .in_col(&collection_name)
+            .precondition(FirestoreWritePrecondition::Exists(true))
+            .document_id(id)
+            .object(&post)
+            .execute()
+    })
+    .await?;
+
+    println!("📌 {} {}", if unpin { "Unpinned" } else { "Pinned" }, id);
+    Ok(())
+}
+
+async fn handle_microblog_pinned(
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let db = connect_firestore().await?;
+    let collection_name = collection("microblog")?;
+
+    let mut posts: Vec<MicroblogStruct> = db
+        .fluent()
+        .select()
+        .from(collection_name.as_str())
+        .filter(|q| q.for_all([q.field(path!(MicroblogStruct::pinned_at)).is_not_null()]))
+        .order_by([(
+            path!(MicroblogStruct::pinned_at),
+            FirestoreQueryDirection::Ascending,
+        )])
+        .obj()
+        .query()
+        .await?;
+    decrypt_posts(&mut posts)?;
+
+    let records = posts
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<_>, _>>()?;
+    let format = if matches.get_flag("json") {
+        "json"
+    } else {
+        "plain"
+    };
+    let fields = ["id", "pinned_at", "content"].map(String::from);
+    print_records(&records, &fields, format)
+}
+
+/// Splits a `--fields` list and checks every name against the fields the record actually has.
+fn parse_fields(
+    requested: Option<&str>,
+    known: &[&str],
+    default: &[&str],
+) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let fields: Vec<String> = match requested {
+        Some(list) => list
+            .split(',')
+            .map(|field| field.trim().to_string())
+            .filter(|field| !field.is_empty())
+            .collect(),
+        None => default.iter().map(|field| field.to_string()).collect(),
+    };
+
+    if fields.is_empty() {
+        return Err("--fields needs at least one field name".into());
+    }
+    if let Some(unknown) = fields.iter().find(|field| !known.contains(&field.as_str())) {
+        return Err(format!(
+            "Unknown field '{}', expected one of: {}",
+            unknown,
+            known.join(", ")
+        )
+        .into());
+    }
+    Ok(fields)
+}
+
+/// Renders a field value on a single line: strings as-is, lists comma-joined, anything else as JSON.
+fn field_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(text) => text.replace('\n', " "),
+        serde_json::Value::Array(items) => {
+            items.iter().map(field_text).collect::<Vec<_>>().join(", ")
+        }
+        other => other.to_string(),
+    }
+}
+
+fn print_records(
+    records: &[serde_json::Value],
+    fields: &[String],
+    format: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let rows: Vec<Vec<&serde_json::Value>> = records
+        .iter()
+        .map(|record| {
+            fields
+                .iter()
+                .map(|field| record.get(field).unwrap_or(&serde_json::Value::Null))
+                .collect()
+        })
+        .collect();
+
+    let stdout = io::stdout();
+    let mut out: Box<dyn Write> = if UNBUFFERED.load(Ordering::SeqCst) {
+        Box::new(stdout.lock())
+    } else {
+        Box::new(BufWriter::new(stdout.lock()))
+    };
+
+    match format {
+        "json" => {
+            let selected: Vec<serde_json::Map<String, serde_json::Value>> = rows
+                .iter()
+                .map(|row| {
+                    fields
+                        .iter()
+                        .cloned()
+                        .zip(row.iter().map(|value| (*value).clone()))
+                        .collect()
+                })
+                .collect();
+            writeln!(out, "{}", serde_json::to_string_pretty(&selected)?)?;
+        }
+        "ndjson" => {
+            for row in &rows {
+                let selected: serde_json::Map<String, serde_json::Value> = fields
+                    .iter()
+                    .cloned()
+                    .zip(row.iter().map(|value| (*value).clone()))
+                    .collect();
+                writeln!(out, "{}", serde_json::to_string(&selected)?)?;
+            }
+        }
+        "table" => {
+            let cells: Vec<Vec<String>> = rows
+                .iter()
+                .map(|row| row.iter().map(|value| field_text(value)).collect())
+                .collect();
+            let widths: Vec<usize> = fields
+                .iter()
+                .enumerate()
+                .map(|(column, field)| {
+                    cells
+                        .iter()
+                        .map(|row| row[column].chars().count())
+                        .chain([field.len()])
+                        .max()
+                        .unwrap_or_default()
+                })
+                .collect();
+
+            let header: Vec<String> = fields
+                .iter()
+                .zip(&widths)
+                .map(|(field, width)| format!("{:<width$}", field, width = width))
+                .collect();
+            writeln!(out, "{}", header.join("  ").trim_end())?;
+            let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+            writeln!(out, "{}", rule.join("  "))?;
+            for row in &cells {
+                let line: Vec<String> = row
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                    .collect();
+                writeln!(out, "{}", line.join("  ").trim_end())?;
+            }
+        }
+        "markdown-table" => {
+            writeln!(out, "| {} |", fields.join(" | "))?;
+            writeln!(out, "|{}", " --- |".repeat(fields.len()))?;
+            for row in &rows {
+                let line: Vec<String> = row
+                    .iter()
+                    .map(|value| field_text(value).replace('|', "\\|").replace('\n', " "))
+                    .collect();
+                writeln!(out, "| {} |", line.join(" | "))?;
+            }
+        }
+        _ => {
+            for row in &rows {
+                let line: Vec<String> = row.iter().map(|value| field_text(value)).collect();
+                writeln!(out, "{}", line.join("\t"))?;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+async fn handle_microblog_export(
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let format = matches.get_one::<String>("format").unwrap();
+    let out = matches.get_one::<String>("out");
+
+    if format == "pdf" && out.is_none() {
+        return Err("The pdf format needs an output file, pass --out <FILE>".into());
+    }
+    #[cfg(not(feature = "pdf"))]
+    if format == "pdf" {
+        return Err(
+            "adot was built without the `pdf` feature, reinstall with `--features pdf`".into(),
+        );
+    }
+
+    // Fail on a missing feed setting before the export is fetched.
+    if format == "atom" {
+        feed_settings()?;
+    }
+
+    let db = connect_firestore().await?;
+    watch_for_ctrl_c();
+    let mut posts = fetch_microblogs(&db).await?;
+    if cancelled() {
+        return Err("Export cancelled, nothing was written".into());
+    }
+    if let Some(visibility) = matches.get_one::<String>("visibility") {
+        posts.retain(|post| post.visibility == *visibility);
+    }
+
+    if matches.contains_id("split") {
+        let dir = Path::new(out.unwrap());
+        fs::create_dir_all(dir)?;
+
+        // Firestore orders epoch numbers before strings and RFC 2822 times by weekday, so a
+        // month's posts aren't necessarily next to each other.
+        let mut months: BTreeMap<String, Vec<MicroblogStruct>> = BTreeMap::new();
+        for post in posts {
+            let month = parse_timestamp(&post.time)
+                .map(|time| time.format("%Y-%m").to_string())
+                .unwrap_or_else(|| "undated".to_string());
+            months.entry(month).or_default().push(post);
+        }
+        for group in months.values_mut() {
+            group.sort_by_key(|post| parse_timestamp(&post.time));
+        }
+
+        let extension = match format.as_str() {
+            "markdown" => "md",
+            "jsonl-firestore" => "jsonl",
+            _ => format,
+        };
+        for (month, group) in &months {
+            let path = dir.join(format!("{}.{}", month, extension));
+            write_export(format, group, Some(&path))?;
+            eprintln!("✅ Exported {} posts to {}", group.len(), path.display());
+        }
+        return Ok(());
+    }
+
+    write_export(format, &posts, out.map(Path::new))?;
+    if let Some(path) = out {
+        eprintln!("✅ Exported {} posts to {}", posts.len(), path);
+    }
+    Ok(())
+}
+
+fn write_export(
+    format: &str,
+    posts: &[MicroblogStruct],
+    out: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let text = match format {
+        #[cfg(feature = "pdf")]
+        "pdf" => return pdf::render_journal(posts, out.unwrap()),
+        "markdown" => render_markdown(posts),
+        "atom" => atom::render(posts, &feed_settings()?)?,
+        "jsonl-firestore" => firestore_jsonl(posts)?,
+        _ => serde_json::to_string_pretty(posts)?,
+    };
+    match out {
+        Some(path) => fs::write(path, text)?,
+        None => println!("{}", text),
+    }
+    Ok(())
+}
+
+/// One Firestore REST `Document` per line: `{"name": "projects/<project>/databases/(default)/
+/// documents/<collection>/<id>", "fields": {...}}` with every field wrapped in its typed value
+/// (`stringValue`, `booleanValue`, `arrayValue`, ...). Each line is a valid body for the REST API's
+/// `documents.patch` on its `name`; `gcloud firestore import` only reads its own export format.
+fn firestore_jsonl(
+    posts: &[MicroblogStruct],
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let project_id = setting("PROJECT_ID", |settings| settings.project_id.as_ref())?;
+    let collection_name = collection("microblog")?;
+
+    let mut lines = Vec::new();
+    for post in posts {
+        let serde_json::Value::Object(fields) = serde_json::to_value(post)? else {
+            unreachable!("posts serialize to objects");
+        };
+        let fields: serde_json::Map<String, serde_json::Value> = fields
+            .into_iter()
+            .filter(|(name, _)| name != "id")
+            .map(|(name, value)| (name, firestore_value(value)))
+            .collect();
+        let document = serde_json::json!({
+            "name": format!(
+                "projects/{}/databases/(default)/documents/{}/{}",
+                project_id, collection_name, post.id
+            ),
+            "fields": fields,
+        });
+        lines.push(serde_json::to_string(&document)?);
+    }
+    Ok(lines.join("\n"))
+}
+
+fn firestore_value(value: serde_json::Value) -> serde_json::Value {
+    use serde_json::{json, Value};
+    match value {
+        Value::Null => json!({ "nullValue": null }),
+        Value::Bool(b) => json!({ "booleanValue": b }),
+        // The REST API encodes 64-bit integers as strings.
+        Value::Number(n) if n.is_i64() || n.is_u64() => json!({ "integerValue": n.to_string() }),
+        Value::Number(n) => json!({ "doubleValue": n }),
+        Value::String(s) => json!({ "stringValue": s }),
+        Value::Array(values) => json!({
+            "arrayValue": { "values": values.into_iter().map(firestore_value).collect::<Vec<_>>() }
+        }),
+        Value::Object(fields) => json!({
+            "mapValue": {
+                "fields": fields
+                    .into_iter()
+                    .map(|(name, value)| (name, firestore_value(value)))
+                    .collect::<serde_json::Map<_, _>>()
+            }
+        }),
+    }
+}
+
+fn feed_settings() -> Result<atom::Feed, Box<dyn std::error::Error + Send + Sync>> {
+    let site_url = setting("ADOT_SITE_URL", |settings| settings.feed.site_url.as_ref())?;
+    let author = setting("ADOT_AUTHOR", |settings| settings.feed.author.as_ref())?;
+    let config = config::get()?;
+    let adot_env = active_env()?;
+
+    let feed_url = config
+        .lookup(&adot_env, |settings| settings.feed.feed_url.as_ref())
+        .unwrap_or_else(|| format!("{}/atom.xml", site_url.trim_end_matches('/')));
+    let title = config
+        .lookup(&adot_env, |settings| settings.feed.title.as_ref())
+        .unwrap_or_else(|| format!("{}'s microblog", author));
+
+    Ok(atom::Feed {
+        title,
+        site_url,
+        feed_url,
+        author,
+    })
+}
+
+/// Renders posts as a markdown archive with a heading per day. Encrypted posts are listed without
+/// their ciphertext.
+fn render_markdown(posts: &[MicroblogStruct]) -> String {
+    let mut text = String::new();
+    let mut current_day: Option<String> = None;
+
+    for post in posts {
+        let (day, clock) = match parse_timestamp(&post.time) {
+            Some(time) => (
+                time.format("%A, %B %-d %Y").to_string(),
+                time.format("%H:%M").to_string(),
+            ),
+            None => (post.time.clone(), String::new()),
+        };
+        if current_day.as_deref() != Some(day.as_str()) {
+            if current_day.is_some() {
+                text.push('\n');
+            }
+            text.push_str(&format!("## {}\n", day));
+            current_day = Some(day);
+        }
+
+        let content = if post.encrypted {
+            "*🔒 encrypted*".to_string()
+        } else {
+            post.content.clone()
+        };
+        text.push_str(&format!("\n### {}\n\n{}\n", clock, content));
+        if !post.tags.is_empty() {
+            let tags = post
+                .tags
+                .iter()
+                .map(|tag| format!("#{}", tag))
+                .collect::<Vec<_>>();
+            text.push_str(&format!("\n{}\n", tags.join(" ")));
+        }
+    }
+    text
+}
+
+#[derive(Debug, Deserialize)]
+struct MicroblogCsvRow {
+    id: String,
+    time: String,
+    content: String,
+    #[serde(default)]
+    tags: String,
+}
+
+async fn handle_microblog_reimport(
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let file = matches.get_one::<String>("file").unwrap();
+    let skip_existing = matches.get_flag("skip-existing");
+    let dry_run = matches.get_flag("dry-run");
+
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_path(file)?;
+    let mut posts = Vec::new();
+    let mut invalid = Vec::new();
+
+    let headers = reader.headers()?.clone();
+    for result in reader.records() {
+        let record = result?;
+        let line = record.position().map(|p| p.line()).unwrap_or_default();
+        let row: MicroblogCsvRow = match record.deserialize(Some(&headers)) {
+            Ok(row) => row,
+            Err(e) => {
+                invalid.push(format!("line {}: {}", line, e));
+                continue;
+            }
+        };
+
+        let problem = if row.id.trim().is_empty() {
+            Some("missing id".to_string())
+        } else if row.content.trim().is_empty() {
+            Some("empty content".to_string())
+        } else if parse_timestamp(&row.time).is_none() {
+            Some(format!(
+                "time '{}' is not a recognizable timestamp",
+                row.time
+            ))
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            invalid.push(format!("line {}: {}", line, problem));
+            continue;
+        }
+
+        posts.push(MicroblogStruct {
+            id: row.id.trim().to_string(),
+            content: row.content,
+            time: parse_timestamp(&row.time).unwrap().to_rfc3339(),
+            tags: row
+                .tags
+                .split(';')
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect(),
+            visibility: default_visibility(),
+            encrypted: false,
+            pinned_at: None,
+            location: None,
+            reply_to: None,
+        });
+    }
+
+    if !invalid.is_empty() {
+        for problem in &invalid {
+            println!("❌ {}", problem);
+        }
+        return Err(format!(
+            "{} invalid rows in {}, nothing was imported",
+            invalid.len(),
+            file
+        )
+        .into());
+    }
+
+    let db = connect_firestore().await?;
+    let collection_name = collection("microblog")?;
+
+    let max_concurrent = *matches.get_one::<u64>("max-concurrent-writes").unwrap() as usize;
+    watch_for_ctrl_c();
+
+    let started = Instant::now();
+    let imports = stream::iter(posts.iter())
+        .map(|post| {
+            let db = &db;
+            let collection_name = &collection_name;
+            async move {
+                if cancelled() {
+                    return Ok(None);
+                }
+
+                let existing: Option<MicroblogStruct> = db
+                    .fluent()
+                    .select()
+                    .by_id_in(collection_name)
+                    .obj()
+                    .one(&post.id)
+                    .await?;
+
+                let action = match existing {
+                    Some(_) if skip_existing => return Ok(Some("Skipped")),
+                    // The CSV holds plaintext, which would replace the ciphertext and leave the post
+                    // readable by anyone with database access.
+                    Some(existing) if existing.encrypted => {
+                        println!(
+                            "🔒 Skipped {}: the stored post is encrypted, use `adot microblog amend` to change it",
+                            post.id
+                        );
+                        return Ok(Some("Skipped"));
+                    }
+                    Some(_) => "Updated",
+                    None => "Inserted",
+                };
+
+                if dry_run {
+                    println!("Would have {}: {}", action.to_lowercase(), post.id);
+                } else if action == "Updated" {
+                    // Only the CSV columns are touched so fields like visibility survive the round trip.
+                    let _: MicroblogStruct = retry_write(|| {
+                        db.fluent()
+                            .update()
+                            .fields(paths!(MicroblogStruct::{content, time, tags}))
+                            .in_col(collection_name)
+                            .document_id(&post.id)
+                            .object(post)
+                            .execute()
+                    })
+                    .await?;
+                    println!("{}: {}", action, post.id);
+                } else {
+                    let _: MicroblogStruct = retry_write(|| {
+                        db.fluent()
+                            .insert()
+                            .into(collection_name)
+                            .document_id(&post.id)
+                            .object(post)
+                            .execute()
+                    })
+                    .await?;
+                    println!("{}: {}", action, post.id);
+                }
+                Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Some(action))
+            }
+        })
+        .buffer_unordered(max_concurrent);
+    let results: Vec<_> = imports.collect().await;
+
+    let mut written = 0;
+    let mut skipped = 0;
+    let mut unprocessed = 0;
+    let mut errors = Vec::new();
+    for result in results {
+        match result {
+            Ok(Some("Skipped")) => skipped += 1,
+            Ok(Some(_)) => written += 1,
+            Ok(None) => unprocessed += 1,
+            Err(e) => errors.push(e.to_string()),
+        }
+    }
+    if !dry_run {
+        print_throughput(written, started);
+    }
+
+    if unprocessed > 0 {
+        println!(
+            "⏹️  Cancelled: {} posts written, {} skipped, {} not processed",
+            written, skipped, unprocessed
+        );
+        return Err("Import cancelled".into());
+    }
+    if !errors.is_empty() {
+        for error in &errors {
+            println!("❌ {}", error);
+        }
+        return Err(format!(
+            "{} of {} posts failed to import ({} written)",
+            errors.len(),
+            posts.len(),
+            written
+        )
+        .into());
+    }
+
+    let verb = if dry_run { "Would import" } else { "Imported" };
+    println!("✅ {} {} posts ({} skipped)", verb, written, skipped);
+    Ok(())
+}
+
+/// Splits batch input into posts, one per line or one per record between `separator` lines. Each
+/// post keeps the line it starts on, for error reports, and empty ones are dropped.
+fn split_batch<'a>(raw: &'a str, separator: Option<&str>) -> Vec<(usize, &'a str)> {
+    let entries: Vec<(usize, &str)> = match separator {
+        Some(separator) => {
+            let mut records = Vec::new();
+            let mut start = 0;
+            let mut start_line = 1;
+            let mut offset = 0;
+            for (index, line) in raw.split_inclusive('\n').enumerate() {
+                if line.trim() == separator {
+                    records.push((start_line, raw[start..offset].trim()));
+                    start = offset + line.len();
+                    start_line = index + 2;
+                }
+                offset += line.len();
+            }
+            records.push((start_line, raw[start..].trim()));
+            records
+        }
+        None => raw
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .collect(),
+    };
+    entries
+        .into_iter()
+        .filter(|(_, content)| !content.is_empty())
+        .collect()
+}
+
+async fn handle_microblog_batch(
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let file = matches.get_one::<String>("file").unwrap();
+    let raw = if file == "-" {
+        let mut buffer = String::new();
+        io::stdin().read_to_string(&mut buffer)?;
+        buffer
+    } else {
+        fs::read_to_string(file)?
+    };
+
+    let db = connect_firestore().await?;
+    let collection_name = collection("microblog")?;
+
+    let separator = matches.get_one::<String>("separator");
+    let entries = split_batch(&raw, separator.map(String::as_str));
+    let keep_raw = matches.get_flag("raw");
+    let id_version = matches.get_one::<String>("id-version").unwrap();
+    // Posts in one batch are spaced a millisecond apart so they keep the input order.
+    let started = Utc::now();
+
+    let max_concurrent = *matches.get_one::<u64>("max-concurrent-writes").unwrap() as usize;
+    watch_for_ctrl_c();
+
+    let started_writes = Instant::now();
+    let writes = stream::iter(entries.iter().enumerate())
+        .map(|(position, (_, content))| {
+            let db = &db;
+            let collection_name = &collection_name;
+            async move {
+                if cancelled() {
+                    return (position, None);
+                }
+
+                let time = started + chrono::Duration::milliseconds(position as i64);
+                let content = if keep_raw {
+                    content.to_string()
+                } else {
+                    normalize_content(content)
+                };
+                let microblog_struct = match apply_plugin(MicroblogStruct {
+                    id: new_post_id(id_version),
+                    content,
+                    time: time.to_rfc3339(),
+                    tags: Vec::new(),
+                    visibility: default_visibility(),
+                    encrypted: false,
+                    pinned_at: None,
+                    location: None,
+                    reply_to: None,
+                }) {
+                    Ok(microblog_struct) => microblog_struct,
+                    Err(e) => return (position, Some(Err(e.to_string()))),
+                };
+
+                let result: Result<MicroblogStruct, _> = retry_write(|| {
+                    db.fluent()
+                        .insert()
+                        .into(collection_name)
+                        .document_id(&microblog_struct.id)
+                        .object(&microblog_struct)
+                        .execute()
+                })
+                .await;
+                (
+                    position,
+                    Some(result.map(|_| ()).map_err(|e| e.to_string())),
+                )
+            }
+        })
+        .buffer_unordered(max_concurrent);
+    let mut results: Vec<(usize, Option<Result<(), String>>)> = writes.collect().await;
+    results.sort_by_key(|(position, _)| *position);
+
+    let mut inserted = 0;
+    let mut failed: Vec<(usize, String, String)> = Vec::new();
+    let mut unprocessed: Vec<(usize, &str)> = Vec::new();
+    for (position, result) in results {
+        let (line, content) = entries[position];
+        match result {
+            Some(Ok(())) => inserted += 1,
+            Some(Err(e)) => failed.push((line, content.to_string(), e)),
+            None => unprocessed.push((line, content)),
+        }
+    }
+    print_throughput(inserted, started_writes);
+
+    println!("✅ Inserted {} posts", inserted);
+    if !unprocessed.is_empty() {
+        println!(
+            "⏹️  Cancelled with {} lines not processed",
+            unprocessed.len()
+        );
+    }
+    if failed.is_empty() && unprocessed.is_empty() {
+        return Ok(());
+    }
+
+    if !failed.is_empty() {
+        println!("❌ {} lines failed:", failed.len());
+        for (line, _, error) in &failed {
+            println!("  line {}: {}", line, error);
+        }
+    }
+
+    if matches.get_flag("retry-queue-on-partial-batch") {
+        let retry_file = matches.get_one::<String>("retry-file").unwrap();
+        let retry_lines: Vec<&str> = failed
+            .iter()
+            .map(|(_, content, _)| content.as_str())
+            .chain(unprocessed.iter().map(|(_, content)| *content))
+            .collect();
+        let (joiner, retry_command) = match separator {
+            Some(separator) => (
+                format!("\n{}\n", separator),
+                format!(
+                    "adot microblog batch --separator '{}' {}",
+                    separator, retry_file
+                ),
+            ),
+            None => (
+                "\n".to_string(),
+                format!("adot microblog batch {}", retry_file),
+            ),
+        };
+        fs::write(retry_file, retry_lines.join(&joiner) + "\n")?;
+        println!(
+            "📝 Wrote {} posts to {}, retry with `{}`",
+            retry_lines.len(),
+            retry_file,
+            retry_command
+        );
+    } else {
+        println!(
+            "💡 Re-run with --retry-queue-on-partial-batch to save the remaining lines for a retry"
+        );
+    }
+
+    if !unprocessed.is_empty() {
+        return Err("Batch cancelled".into());
+    }
+    Err(format!(
+        "{} of {} posts failed to insert",
+        failed.len(),
+        inserted + failed.len()
+    )
+    .into())
+}
+
+fn print_throughput(writes: usize, started: Instant) {
+    let seconds = started.elapsed().as_secs_f64();
+    println!(
+        "📈 {} writes in {:.1}s ({:.1}/s)",
+        writes,
+        seconds,
+        writes as f64 / seconds.max(0.001)
+    );
+}
+
+/// Prints the fields that differ between two serialized documents, returning whether any did.
+fn print_document_diff(old: &serde_json::Value, new: &serde_json::Value) -> bool {
+    let empty = serde_json::Map::new();
+    let old_fields = old.as_object().unwrap_or(&empty);
+    let new_fields = new.as_object().unwrap_or(&empty);
+    let keys: BTreeSet<&String> = old_fields.keys().chain(new_fields.keys()).collect();
+
+    let mut changed = false;
+    for key in keys {
+        let old_field = old_fields
+            .get(key.as_str())
+            .unwrap_or(&serde_json::Value::Null);
+        let new_field = new_fields
+            .get(key.as_str())
+            .unwrap_or(&serde_json::Value::Null);
+        if old_field != new_field {
+            println!("{}:\n  - {}\n  + {}", key, old_field, new_field);
+            changed = true;
+        }
+    }
+    changed
+}
+
+async fn handle_location(
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let timestamp = Utc::now().to_rfc3339();
+
+    let ip = matches.get_one::<IpAddr>("ip");
+    if let Some(ip) = ip {
+        if !is_public_ip(ip) {
+            eprintln!("⚠️  {} is a private or reserved address", ip);
+            return Err(
+                format!("ipinfo.io has no location for {}, nothing was recorded", ip).into(),
+            );
+        }
+    }
+
+    let ipinfo_token = setting("IPINFO_TOKEN", |settings| settings.ipinfo_token.as_ref())?;
+
+    let db = connect_firestore().await?;
+    let collection_name = collection("location")?;
+
+    let change_only = matches.get_flag("history-on-change-only");
+    let previous: Option<LocationStruct> = if change_only {
+        db.fluent()
+            .select()
+            .by_id_in(&collection_name)
+            .obj()
+            .one("latest")
+            .await?
+    } else {
+        None
+    };
+
+    // Deleting first leaves no 'latest' at all if the lookup or the insert below fails.
+    let in_place = matches.get_flag("in-place");
+    if !in_place {
+        deprecated("deleting 'latest' before each check-in");
+        println!("🗑️  Cleaning up existing location entry...");
+        if retry_write(|| {
+            db.fluent()
+                .delete()
+                .from(&collection_name)
+                .document_id("latest")
+                .execute()
+        })
+        .await
+        .is_ok()
+        {
+            println!("Deleted existing 'latest' entry");
+        }
+    }
+
+    println!("📍 Fetching location data from ipinfo.io...");
+    let location_data = fetch_ipinfo(&ipinfo_token, ip).await?;
+    let mut location_struct = location_from_ipinfo(
+        &location_data,
+        matches.get_flag("coordinates-only"),
+        matches.get_one::<String>("transport").cloned(),
+        timestamp,
+    )?;
+    if matches.get_flag("timezone-from-system") {
+        match system_timezone() {
+            Ok(timezone) => location_struct.timezone = timezone,
+            Err(e) => eprintln!("⚠️  {}, keeping ipinfo's {}", e, location_struct.timezone),
+        }
+    }
+    if matches.get_flag("store-raw") {
+        location_struct.raw = Some(location_data);
+    }
+
+    let object_returned: LocationStruct = if in_place {
+        // An update without a precondition creates the document if it doesn't exist yet.
+        retry_write(|| {
+            db.fluent()
+                .update()
+                .in_col(&collection_name)
+                .document_id("latest")
+                .object(&location_struct)
+                .execute()
+        })
+        .await?
+    } else {
+        retry_write(|| {
+            db.fluent()
+                .insert()
+                .into(&collection_name)
+                .document_id("latest")
+                .object(&location_struct)
+                .execute()
+        })
+        .await?
+    };
+
+    println!("✅ Updated location: {:?}", object_returned);
+
+    let record_history = if change_only {
+        let changed = previous
+            .as_ref()
+            .is_none_or(|previous| location_changed(previous, &location_struct));
+        if !changed {
+            println!("⏸️  Location unchanged, not appending to history");
+        }
+        changed
+    } else {
+        matches.get_flag("history")
+    };
+
+    if record_history {
+        let history_collection = collection("location_history")?;
+        let _: LocationStruct = retry_write(|| {
+            db.fluent()
+                .insert()
+                .into(&history_collection)
+                .document_id(Uuid::now_v7().to_string())
+                .object(&location_struct)
+                .execute()
+        })
+        .await?;
+        println!("🗂️  Appended to location history");
+    }
+    Ok(())
+}
+
+/// The current location for `microblog --with-location`. A post matters more than its location,
+/// so any failure is a warning and the post goes out without one.
+async fn post_location(timestamp: &str) -> Option<LocationStruct> {
+    let location = async {
+        let ipinfo_token = setting("IPINFO_TOKEN", |settings| settings.ipinfo_token.as_ref())?;
+        let location_data = fetch_ipinfo(&ipinfo_token, None).await?;
+        location_from_ipinfo(&location_data, false, None, timestamp.to_string())
+    };
+    match location.await {
+        Ok(location) => {
+            println!("📍 Tagged with {}, {}", location.city, location.country);
+            Some(location)
+        }
+        Err(e) => {
+            eprintln!("⚠️  Could not get your location, posting without it: {}", e);
+            None
+        }
+    }
+}
+
+/// Asks ipinfo.io where `ip` is, or where this machine is without one.
+async fn fetch_ipinfo(
+    token: &str,
+    ip: Option<&IpAddr>,
+) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+    let url = match ip {
+        Some(ip) => format!("https://ipinfo.io/{}/json?token={}", ip, token),
+        None => format!("https://ipinfo.io/json?token={}", token),
+    };
+
+    let response = reqwest::get(&url).await?;
+    let status = response.status();
+
+    if !status.is_success() {
+        let error_text = response.text().await?;
+        return Err(format!("API request failed with status {}: {}", status, error_text).into());
+    }
+
+    Ok(response.json().await?)
+}
+
+/// Builds a location from an ipinfo response. With `coordinates_only` the place names are left
+/// empty and only coordinates and timezone are kept.
+fn location_from_ipinfo(
+    location_data: &serde_json::Value,
+    coordinates_only: bool,
+    transport: Option<String>,
+    timestamp: String,
+) -> Result<LocationStruct, Box<dyn std::error::Error + Send + Sync>> {
+    let (lat, lon) = match location_data["loc"].as_str().and_then(parse_loc) {
+        Some((lat, lon)) => (Some(lat), Some(lon)),
+        None => (None, None),
+    };
+
+    let mut location_struct = if coordinates_only {
+        if lat.is_none() {
+            return Err("Missing or malformed loc field, cannot store coordinates".into());
+        }
+        LocationStruct {
+            city: String::new(),
+            region: String::new(),
+            country: String::new(),
+            timezone: location_data["timezone"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            time: TimeStruct { utc: timestamp },
+            transport,
+            lat,
+            lon,
+            accuracy: None,
+            org: None,
+            raw: None,
+        }
+    } else {
+        LocationStruct {
+            city: location_data["city"]
+                .as_str()
+                .ok_or("Missing city field")?
+                .to_string(),
+            region: location_data["region"]
+                .as_str()
+                .ok_or("Missing region field")?
+                .to_string(),
+            country: location_data["country"]
+                .as_str()
+                .ok_or("Missing country field")?
+                .to_string(),
+            timezone: location_data["timezone"]
+                .as_str()
+                .ok_or("Missing timezone field")?
+                .to_string(),
+            time: TimeStruct { utc: timestamp },
+            transport,
+            lat,
+            lon,
+            accuracy: None,
+            org: location_data["org"].as_str().map(str::to_string),
+            raw: None,
+        }
+    };
+    derive_location_fields(&mut location_struct);
+    Ok(location_struct)
+}
+
+/// The OS timezone, checked against the IANA database.
+fn system_timezone() -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let timezone = iana_time_zone::get_timezone()
+        .map_err(|e| format!("Could not detect the system timezone ({})", e))?;
+    if timezone.parse::<chrono_tz::Tz>().is_err() {
+        return Err(format!("System timezone '{}' is not an IANA zone", timezone).into());
+    }
+    Ok(timezone)
+}
+
+/// Whether an address is globally routable, so ipinfo.io can place it. Covers the private,
+/// loopback, link-local, shared, documentation, multicast and reserved ranges.
+fn is_public_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_documentation()
+                || v4.is_multicast()
+                || (a == 100 && (64..128).contains(&b))
+                || a == 0
+                || a >= 240)
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public_ip(&IpAddr::V4(v4));
+            }
+            let first = v6.segments()[0];
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                || first & 0xfe00 == 0xfc00
+                || first & 0xffc0 == 0xfe80
+                || (first == 0x2001 && v6.segments()[1] == 0x0db8))
+        }
+    }
+}
+
+/// Whether two check-ins are at different places, ignoring time and transport.
+fn location_changed(previous: &LocationStruct, current: &LocationStruct) -> bool {
+    previous.city != current.city
+        || previous.region != current.region
+        || previous.country != current.country
+        || previous.lat != current.lat
+        || previous.lon != current.lon
+}
+
+/// Fills in the fields computed from the others. New derived fields belong here so that
+/// `adot location backfill` picks them up for older documents too.
+fn derive_location_fields(location: &mut LocationStruct) {
+    // ipinfo's coordinates are city-level, so they count as city accuracy on their own.
+    let accuracy = if !location.city.is_empty() || location.lat.is_some() {
+        "city"
+    } else if !location.region.is_empty() {
+        "region"
+    } else if !location.country.is_empty() {
+        "country"
+    } else {
+        "none"
+    };
+    location.accuracy = Some(accuracy.to_string());
+}
+
+async fn handle_location_backfill(
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let dry_run = matches.get_flag("dry-run");
+    let db = connect_firestore().await?;
+
+    let mut updated = 0;
+    let mut unchanged = 0;
+    let mut unreadable = 0;
+
+    for base in ["location", "location_history"] {
+        let collection_name = collection(base)?;
+        let documents = db
+            .fluent()
+            .select()
+            .from(collection_name.as_str())
+            .query()
+            .await?;
+
+        for document in &documents {
+            let id = document.name.rsplit('/').next().unwrap_or_default();
+            let Ok(stored) = FirestoreDb::deserialize_doc_to::<LocationStruct>(document) else {
+                println!(
+                    "⚠️  {}/{} is not a readable location, skipping",
+                    collection_name, id
+                );
+                unreadable += 1;
+                continue;
+            };
+
+            let mut derived = stored.clone();
+            derive_location_fields(&mut derived);
+            if derived.accuracy == stored.accuracy {
+                unchanged += 1;
+                continue;
+            }
+
+            println!(
+                "{}/{}: accuracy {:?} → {:?}",
+                collection_name, id, stored.accuracy, derived.accuracy
+            );
+            updated += 1;
+            if dry_run {
+                continue;
+            }
+
+            let _: LocationStruct = retry_write(|| {
+                db.fluent()
+                    .update()
+                    .fields(paths!(LocationStruct::{accuracy}))
+                    .in_col(&collection_name)
+                    .document_id(id)
+                    .object(&derived)
+                    .execute()
+            })
+            .await?;
+        }
+    }
+
+    let verb = if dry_run { "Would update" } else { "Updated" };
+    println!(
+        "✅ {} {} locations ({} already current, {} unreadable)",
+        verb, updated, unchanged, unreadable
+    );
+    Ok(())
+}
+
+/// Orders accuracy levels from coarsest to finest, so `city` ranks highest.
+fn accuracy_rank(accuracy: Option<&str>) -> u8 {
+    match accuracy {
+        Some("city") => 3,
+        Some("region") => 2,
+        Some("country") => 1,
+        _ => 0,
+    }
+}
+
+/// Parses ipinfo's `loc` field, which is `"lat,lon"`.
+fn parse_loc(loc: &str) -> Option<(f64, f64)> {
+    let (lat, lon) = loc.split_once(',')?;
+    Some((lat.trim().parse().ok()?, lon.trim().parse().ok()?))
+}
+
+async fn handle_location_forget(
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let city = matches.get_one::<String>("city");
+    let country = matches.get_one::<String>("country");
+    let is_match = |location: &LocationStruct| {
+        city.is_none_or(|city| location.city.eq_ignore_ascii_case(city))
+            && country.is_none_or(|country| location.country.eq_ignore_ascii_case(country))
+    };
+
+    let db = connect_firestore().await?;
+    let history_collection = collection("location_history")?;
+    let documents = db
+        .fluent()
+        .select()
+        .from(history_collection.as_str())
+        .query()
+        .await?;
+
+    let mut matching = Vec::new();
+    for document in &documents {
+        let Ok(location) = FirestoreDb::deserialize_doc_to::<LocationStruct>(document) else {
+            continue;
+        };
+        if is_match(&location) {
+            let id = document.name.rsplit('/').next().unwrap_or_default();
+            matching.push((id.to_string(), location));
+        }
+    }
+    matching.sort_by(|(_, a), (_, b)| a.time.utc.cmp(&b.time.utc));
+
+    let place = [city, country]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if matching.is_empty() {
+        println!("✅ No history entries match {}", place);
+        return Ok(());
+    }
+
+    let (first, last) = (&matching[0].1, &matching[matching.len() - 1].1);
+    println!(
+        "📍 {} of {} history entries match {} ({} to {})",
+        matching.len(),
+        documents.len(),
+        place,
+        first.time.utc,
+        last.time.utc
+    );
+
+    let latest: Option<LocationStruct> = db
+        .fluent()
+        .select()
+        .by_id_in(&collection("location")?)
+        .obj()
+        .one("latest")
+        .await?;
+    if latest.as_ref().is_some_and(is_match) {
+        println!("⚠️  Your latest check-in is also there; it stays until the next `adot location`");
+    }
+
+    if matches.get_flag("dry-run") {
+        println!("🔍 Dry run, nothing was deleted");
+        return Ok(());
+    }
+    if !matches.get_flag("yes") && !confirm("Delete these entries for good?")? {
+        println!("❌ Nothing was deleted");
+        return Ok(());
+    }
+
+    watch_for_ctrl_c();
+    let mut deleted = 0;
+    for (id, _) in &matching {
+        if cancelled() {
+            break;
+        }
+        retry_write(|| {
+            db.fluent()
+                .delete()
+                .from(&history_collection)
+                .document_id(id)
+                .execute()
+        })
+        .await?;
+        deleted += 1;
+    }
+
+    println!("🗑️  Deleted {} of {} entries", deleted, matching.len());
+    if cancelled() && deleted < matching.len() {
+        return Err("Forget cancelled".into());
+    }
+    Ok(())
+}
+
+async fn fetch_location_history(
+    db: &FirestoreDb,
+) -> Result<Vec<LocationStruct>, Box<dyn std::error::Error + Send + Sync>> {
+    let history_collection = collection("location_history")?;
+
+    let history: Vec<LocationStruct> = db
+        .fluent()
+        .select()
+        .from(history_collection.as_str())
+        .order_by([("time.utc", FirestoreQueryDirection::Ascending)])
+        .obj()
+        .query()
+        .await?;
+
+    Ok(history)
+}
+
+async fn handle_location_export(
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let format = matches.get_one::<String>("format").unwrap();
+    let out = matches.get_one::<String>("out");
+
+    let db = connect_firestore().await?;
+    watch_for_ctrl_c();
+    let mut history = fetch_location_history(&db).await?;
+    if cancelled() {
+        return Err("Export cancelled, nothing was written".into());
+    }
+
+    if let Some(min_accuracy) = matches.get_one::<String>("min-accuracy") {
+        let minimum = accuracy_rank(Some(min_accuracy));
+        let before = history.len();
+        history.retain(|location| {
+            // Entries from before accuracy was stored get it derived here instead of dropped.
+            let mut derived = location.clone();
+            if derived.accuracy.is_none() {
+                derive_location_fields(&mut derived);
+            }
+            accuracy_rank(derived.accuracy.as_deref()) >= minimum
+        });
+        eprintln!(
+            "🔎 Filtered out {} of {} entries coarser than {}",
+            before - history.len(),
+            before,
+            min_accuracy
+        );
+    }
+
+    let output = match format.as_str() {
+        "influx" => {
+            let lines: Vec<String> = history.iter().filter_map(influx_line).collect();
+            let skipped = history.len() - lines.len();
+            if skipped > 0 {
+                eprintln!(
+                    "⚠️  Skipped {} entries without coordinates or a valid time",
+                    skipped
+                );
+            }
+            lines.join("\n")
+        }
+        "csv" => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            writer.write_record([
+                "time", "city", "region", "country", "timezone", "lat", "lon", "org",
+            ])?;
+            for location in &history {
+                writer.serialize((
+                    &location.time.utc,
+                    &location.city,
+                    &location.region,
+                    &location.country,
+                    &location.timezone,
+                    location.lat,
+                    location.lon,
+                    &location.org,
+                ))?;
+            }
+            String::from_utf8(writer.into_inner()?)?
+                .trim_end()
+                .to_string()
+        }
+        _ => serde_json::to_string_pretty(&history)?,
+    };
+
+    let mut bytes = (output + "\n").into_bytes();
+    if matches.get_flag("gzip") {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&bytes)?;
+        bytes = encoder.finish()?;
+    }
+
+    match out {
+        Some(path) => {
+            fs::write(path, bytes)?;
+            eprintln!("✅ Exported {} locations to {}", history.len(), path);
+        }
+        None => io::stdout().write_all(&bytes)?,
+    }
+    Ok(())
+}
+
+/// Formats a location as an InfluxDB line protocol point, or `None` if it has no coordinates.
+fn influx_line(location: &LocationStruct) -> Option<String> {
+    let escape = |value: &str| {
+        value
+            .replace('\\', "\\\\")
+            .replace(',', "\\,")
+            .replace('=', "\\=")
+            .replace(' ', "\\ ")
+    };
+
+    let (lat, lon) = (location.lat?, location.lon?);
+    let nanos = parse_timestamp(&location.time.utc)?.timestamp_nanos_opt()?;
+
+    let mut tags = String::new();
+    for (key, value) in [("city", &location.city), ("country", &location.country)] {
+        if !value.is_empty() {
+            tags.push_str(&format!(",{}={}", key, escape(value)));
+        }
+    }
+    Some(format!(
+        "location{} lat={},lon={} {}",
+        tags, lat, lon, nanos
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+enum RpcCommand {
+    Ping,
+    Microblog {
+        content: String,
+        #[serde(default = "default_visibility")]
+        visibility: String,
+        #[serde(default)]
+        tags: Vec<String>,
+        #[serde(default)]
+        raw: bool,
+        #[serde(default)]
+        id_version: Option<u8>,
+    },
+    List {
+        limit: Option<u32>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcRequest {
+    #[serde(default)]
+    id: serde_json::Value,
+    #[serde(flatten)]
+    command: RpcCommand,
+}
+
+async fn handle_rpc() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use tokio::io::AsyncBufReadExt;
+
+    let db = connect_firestore().await?;
+    let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<RpcRequest>(&line) {
+            Ok(request) => match run_rpc_command(&db, request.command).await {
+                Ok(result) => serde_json::json!({ "id": request.id, "ok": true, "result": result }),
+                Err(e) => {
+                    serde_json::json!({ "id": request.id, "ok": false, "error": e.to_string() })
+                }
+            },
+            Err(e) => {
+                // Echo the id back even when the rest of the request is malformed.
+                let id = serde_json::from_str::<serde_json::Value>(&line)
+                    .ok()
+                    .and_then(|value| value.get("id").cloned())
+                    .unwrap_or_default();
+                serde_json::json!({ "id": id, "ok": false, "error": format!("Invalid request: {}", e) })
+            }
+        };
+        println!("{}", response);
+    }
+    Ok(())
+}
+
+async fn run_rpc_command(
+    db: &FirestoreDb,
+    command: RpcCommand,
+) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+    let collection_name = collection("microblog")?;
+
+    match command {
+        RpcCommand::Ping => Ok(serde_json::json!("pong")),
+        RpcCommand::Microblog {
+            content,
+            visibility,
+            tags,
+            raw,
+            id_version,
+        } => {
+            let id_version = match id_version {
+                None | Some(4) => "4",
+                Some(7) => "7",
+                Some(other) => {
+                    return Err(format!("Invalid id_version {}, expected 4 or 7", other).into())
+                }
+            };
+            let content = if raw {
+                content
+            } else {
+                normalize_content(&content)
+            };
+            if content.is_empty() {
+                return Err("Microblog content is empty".into());
+            }
+            if !VISIBILITIES.contains(&visibility.as_str()) {
+                return Err(format!(
+                    "Invalid visibility '{}', expected one of: {}",
+                    visibility,
+                    VISIBILITIES.join(", ")
+                )
+                .into());
+            }
+
+            let microblog_struct = apply_plugin(MicroblogStruct {
+                id: new_post_id(id_version),
+                content,
+                time: Utc::now().to_rfc3339(),
+                tags,
+                visibility,
+                encrypted: false,
+                pinned_at: None,
+                location: None,
+                reply_to: None,
+            })?;
+            let object_returned: MicroblogStruct = retry_write(|| {
+                db.fluent()
+                    .insert()
+                    .into(&collection_name)
+                    .document_id(&microblog_struct.id)
+                    .object(&microblog_struct)
+                    .execute()
+            })
+            .await?;
+            Ok(serde_json::to_value(object_returned)?)
+        }
+        RpcCommand::List { limit } => {
+            let mut query = db
+                .fluent()
+                .select()
+                .from(collection_name.as_str())
+                .order_by([(
+                    path!(MicroblogStruct::time),
+                    FirestoreQueryDirection::Descending,
+                )]);
+            if let Some(limit) = limit {
+                query = query.limit(limit);
+            }
+            let posts: Vec<MicroblogStruct> = query.obj().query().await?;
+            Ok(serde_json::to_value(posts)?)
+        }
+    }
+}
+
+/// Collections larger than this are sized from their first `USAGE_SAMPLE` documents.
+const USAGE_SAMPLE: u32 = 500;
+
+#[derive(Debug, Deserialize)]
+struct CountResult {
+    count: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct CollectionUsage {
+    collection: String,
+    documents: usize,
+    bytes: usize,
+    sampled: bool,
+}
+
+async fn handle_usage(
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let db = connect_firestore().await?;
+
+    let mut usage = Vec::new();
+    for base in ["microblog", "location", "location_history"] {
+        let collection_name = collection(base)?;
+        let counts: Vec<CountResult> = db
+            .fluent()
+            .select()
+            .from(collection_name.as_str())
+            .aggregate(|a| a.fields([a.field(path!(CountResult::count)).count()]))
+            .obj()
+            .query()
+            .await?;
+        let documents = counts.first().map(|result| result.count).unwrap_or(0);
+
+        let sample = db
+            .fluent()
+            .select()
+            .from(collection_name.as_str())
+            .limit(USAGE_SAMPLE)
+            .query()
+            .await?;
+        // The document path plus its fields as JSON; close to, not exactly, what Firestore bills.
+        let mut sample_bytes = 0;
+        for document in &sample {
+            let mut fields = FirestoreDb::deserialize_doc_to::<serde_json::Value>(document)?;
+            // The deserializer adds the id, path and timestamps as `_firestore_*` keys, which
+            // aren't stored fields and would count the path twice.
+            if let Some(object) = fields.as_object_mut() {
+                object.retain(|key, _| !key.starts_with("_firestore_"));
+            }
+            sample_bytes += document.name.len() + serde_json::to_vec(&fields)?.len();
+        }
+        let sampled = documents > sample.len();
+        let bytes = if sampled {
+            sample_bytes * documents / sample.len().max(1)
+        } else {
+            sample_bytes
+        };
+
+        usage.push(CollectionUsage {
+            collection: collection_name,
+            documents,
+            bytes,
+            sampled,
+        });
+    }
+
+    if matches.get_flag("json") {
+        println!("{}", serde_json::to_string_pretty(&usage)?);
+        return Ok(());
+    }
+
+    for entry in &usage {
+        println!(
+            "📦 {}: {} documents, {}{}",
+            entry.collection,
+            entry.documents,
+            if entry.sampled { "~" } else { "" },
+            human_bytes(entry.bytes)
+        );
+    }
+    println!(
+        "✅ Total: {} documents, {}",
+        usage.iter().map(|entry| entry.documents).sum::<usize>(),
+        human_bytes(usage.iter().map(|entry| entry.bytes).sum())
+    );
+    if usage.iter().any(|entry| entry.sampled) {
+        println!(
+            "💡 Sizes marked ~ are extrapolated from the first {} documents",
+            USAGE_SAMPLE
+        );
+    }
+    Ok(())
+}
+
+fn human_bytes(bytes: usize) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+async fn handle_migrate_timestamps(
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let dry_run = matches.get_flag("dry-run");
+    let db = connect_firestore().await?;
+
+    let mut migrated = 0;
+    let mut unparseable = Vec::new();
+
+    for (base, field_path) in [
+        ("microblog", "time"),
+        ("location", "time.utc"),
+        ("location_history", "time.utc"),
+    ] {
+        let collection_name = collection(base)?;
+        let documents = db
+            .fluent()
+            .select()
+            .from(collection_name.as_str())
+            .query()
+            .await?;
+
+        for document in &documents {
+            let fields: serde_json::Value = FirestoreDb::deserialize_doc_to(document)?;
+            let id = fields["_firestore_id"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            let stored = field_path
+                .split('.')
+                .fold(&fields, |value, key| &value[key]);
+
+            let raw = match stored {
+                serde_json::Value::String(text) => text.clone(),
+                serde_json::Value::Number(number) => number.to_string(),
+                other => {
+                    unparseable.push(format!("{}/{}: {}", collection_name, id, other));
+                    continue;
+                }
+            };
+            if stored.is_string() && DateTime::parse_from_rfc3339(&raw).is_ok() {
+                continue;
+            }
+            let Some(time) = parse_timestamp(&raw) else {
+                unparseable.push(format!("{}/{}: {}", collection_name, id, stored));
+                continue;
+            };
+
+            let canonical = time.to_rfc3339();
+            println!("{}/{}: {} → {}", collection_name, id, stored, canonical);
+            migrated += 1;
+            if dry_run {
+                continue;
+            }
+
+            let patch = field_path.rsplit('.').fold(
+                serde_json::Value::String(canonical),
+                |value, key| serde_json::json!({ key: value }),
+            );
+            let _: serde_json::Value = retry_write(|| {
+                db.fluent()
+                    .update()
+                    .fields([field_path])
+                    .in_col(&collection_name)
+                    .document_id(&id)
+                    .object(&patch)
+                    .execute()
+            })
+            .await?;
+        }
+    }
+
+    let verb = if dry_run { "Would migrate" } else { "Migrated" };
+    println!("✅ {} {} timestamps", verb, migrated);
+    if !unparseable.is_empty() {
+        println!("❌ {} timestamps could not be parsed:", unparseable.len());
+        for entry in &unparseable {
+            println!("  {}", entry);
+        }
+    }
+    Ok(())
+}
+
+/// Asks for one `adot init` value until `check` accepts it, or takes it from its flag with
+/// `--non-interactive`.
+fn init_value<F>(
+    matches: &ArgMatches,
+    flag: &str,
+    label: &str,
+    secret: bool,
+    check: F,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>>
+where
+    F: Fn(&str) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+{
+    if matches.get_flag("non-interactive") {
+        let value = matches
+            .get_one::<String>(flag)
+            .ok_or_else(|| format!("--non-interactive needs --{}", flag))?;
+        check(value).map_err(|e| format!("--{}: {}", flag, e))?;
+        return Ok(value.to_string());
+    }
+
+    loop {
+        let value = match matches.get_one::<String>(flag) {
+            Some(value) => value.to_string(),
+            None if secret => rpassword::prompt_password(format!("{}: ", label))?,
+            None => {
+                print!("{}: ", label);
+                io::stdout().flush()?;
+                let mut answer = String::new();
+                io::stdin().read_line(&mut answer)?;
+                answer.trim().to_string()
+            }
+        };
+        match check(&value) {
+            Ok(()) => return Ok(value),
+            // A bad value passed as a flag would fail the same way forever.
+            Err(e) if matches.contains_id(flag) => return Err(format!("--{}: {}", flag, e).into()),
+            Err(e) => println!("❌ {}", e),
+        }
+    }
+}
+
+async fn handle_init(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let path = config::path();
+    if path.exists() && !matches.get_flag("force") {
+        return Err(format!(
+            "{} already exists, pass --force to overwrite it",
+            path.display()
+        )
+        .into());
+    }
+
+    let project_id = init_value(
+        matches,
+        "project-id",
+        "🔥 Firebase project id",
+        false,
+        |id| {
+            if id.is_empty()
+                || !id
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            {
+                return Err("Project ids are lowercase letters, digits and '-'".into());
+            }
+            Ok(())
+        },
+    )?;
+
+    let credentials = init_value(
+        matches,
+        "credentials",
+        "🔑 Service account key file",
+        false,
+        |file| {
+            let raw =
+                fs::read_to_string(file).map_err(|e| format!("Cannot read {}: {}", file, e))?;
+            let key: serde_json::Value =
+                serde_json::from_str(&raw).map_err(|_| format!("{} is not JSON", file))?;
+            if key["type"] != "service_account" {
+                return Err(format!("{} is not a service account key", file).into());
+            }
+            if key["project_id"] != project_id.as_str() {
+                println!(
+                    "⚠️  The key belongs to project {}, not {}",
+                    key["project_id"].as_str().unwrap_or("?"),
+                    project_id
+                );
+            }
+            Ok(())
+        },
+    )?;
+    let credentials = fs::canonicalize(&credentials)?.display().to_string();
+
+    let ipinfo_token = init_value(
+        matches,
+        "ipinfo-token",
+        "📍 ipinfo.io token",
+        true,
+        |_| Ok(()),
+    )?;
+    println!("📍 Checking the ipinfo token...");
+    let response = reqwest::get(format!("https://ipinfo.io/json?token={}", ipinfo_token)).await?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "ipinfo.io rejected the token with status {}",
+            response.status()
+        )
+        .into());
+    }
+
+    let quote = |value: &str| toml::Value::String(value.to_string()).to_string();
+    let contents = config::TEMPLATE
+        .replace(
+            "project_id = \"your-project-id\"",
+            &format!("project_id = {}", quote(&project_id)),
+        )
+        .replace(
+            "google_application_credentials = \"/path/to/service-account.json\"",
+            &format!("google_application_credentials = {}", quote(&credentials)),
+        )
+        .replace(
+            "ipinfo_token = \"your-ipinfo-token\"",
+            &format!("ipinfo_token = {}", quote(&ipinfo_token)),
+        );
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, contents)?;
+    println!("✅ Wrote config to {}", path.display());
+
+    // Environment variables beat the config file, so set them to test exactly what was entered.
+    unsafe {
+        env::set_var("PROJECT_ID", &project_id);
+        env::set_var("GOOGLE_APPLICATION_CREDENTIALS", &credentials);
+    }
+    println!("🧪 Test run: reading the microblog collection, nothing is written...");
+    let db = connect_firestore().await?;
+    let posts = db
+        .fluent()
+        .select()
+        .from(collection("microblog")?.as_str())
+        .limit(1)
+        .query()
+        .await
+        .map_err(|e| format!("Firestore test failed, check the project and key: {}", e))?;
+    println!(
+        "✅ Connected to Firestore ({})",
+        if posts.is_empty() {
+            "no posts yet"
+        } else {
+            "found your posts"
+        }
+    );
+    println!("🎉 All set, try `adot microblog 'hello world'`");
+    Ok(())
+}
+
+fn handle_config_init(
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let path = config::path();
+
+    if path.exists() && !matches.get_flag("force") {
+        return Err(format!(
+            "{} already exists, pass --force to overwrite it",
+            path.display()
+        )
+        .into());
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, config::TEMPLATE)?;
+
+    println!("✅ Wrote config template to {}", path.display());
+    println!(
+        "📝 Fill in the placeholders, then try `adot microblog export` to check the connection"
+    );
+    Ok(())
+}
+
+fn handle_config_show() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let path = config::path();
+    let adot_env = active_env()?;
+    let profile = if config::get()?.profiles.contains_key(&adot_env) {
+        "its own profile"
+    } else if adot_env == "prod" {
+        "top-level settings"
+    } else {
+        "top-level settings, prefixed collections"
+    };
+
+    println!("🌐 Environment: {} ({})", adot_env, profile);
+    println!(
+        "📝 Config file: {}{}",
+        path.display(),
+        if path.exists() { "" } else { " (not found)" }
+    );
+    match setting("PROJECT_ID", |settings| settings.project_id.as_ref()) {
+        Ok(project_id) => println!("🔥 Project: {}", project_id),
+        Err(_) => println!("🔥 Project: not set"),
+    }
+    for base in ["microblog", "location", "location_history"] {
+        println!("📦 {}: {}", base, collection(base)?);
+    }
+    Ok(())
+}
+
+fn handle_readme(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let current_dir = env::current_dir()?;
+    let readme_path = current_dir.join(matches.get_one::<String>("append-footer-to").unwrap());
+    let file_name = readme_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or("The footer target must be a file")?;
+    // The footer links the logos relatively, so they live next to the target file.
+    let akshithio_dir = readme_path
+        .parent()
+        .unwrap_or(&current_dir)
+        .join("akshithio");
+    let month = Utc::now().format("%b").to_string().to_lowercase();
+    let year = Utc::now().format("%Y");
+
+    let caption = if let Some(custom_caption) = matches.get_one::<String>("caption") {
+        format!("{} - {} {}", custom_caption, month, year)
+    } else {
+        format!("hello world! - {} {}", month, year)
+    };
+
+    let prepend = matches
+        .get_one::<String>("prepend")
+        .map(|text| format!("{}\n\n", text))
+        .unwrap_or_default();
+    let footer = format!("\n<br />\n\n{}&nbsp;<img src=\"./akshithio/light-logo.png#gh-dark-mode-only\" alt=\"Akshith Garapati's Personal Icon - Doodle of Two Eyes Dark Mode\" width =\"24px\" align = \"left\" /><img src=\"./akshithio/dark-logo.png#gh-light-mode-only\" alt=\"Akshith Garapati's Personal Icon - Doodle of Two Eyes Dark Mode\" width =\"24px\" align = \"left\" /> {} ", prepend, caption);
+
+    let existing = if readme_path.exists() {
+        Some(fs::read_to_string(&readme_path)?)
+    } else {
+        None
+    };
+    let (updated, message) = match &existing {
+        Some(content) => match content.rfind("./akshithio/light-logo.png") {
+            None => (
+                Some(format!("{}{}", content.trim_end(), footer)),
+                format!("✅ Added footer to existing {}", file_name),
+            ),
+            Some(logo) if matches.get_flag("update") => {
+                // The footer runs from its `<br />` to the end of the line holding the logos.
+                let start = content[..logo].rfind("<br />").unwrap_or(logo);
+                let end = content[logo..]
+                    .find('\n')
+                    .map(|offset| logo + offset)
+                    .unwrap_or(content.len());
+                let rewritten = format!(
+                    "{}{}{}",
+                    content[..start].trim_end(),
+                    footer,
+                    &content[end..]
+                );
+                if rewritten == *content {
+                    (
+                        None,
+                        format!("✅ Footer in {} is already up to date", file_name),
+                    )
+                } else {
+                    (
+                        Some(rewritten),
+                        format!("✅ Updated footer in {}", file_name),
+                    )
+                }
+            }
+            Some(_) => (
+                None,
+                format!(
+                    "⚠️  Footer already exists in {} (pass --update to rewrite it)",
+                    file_name
+                ),
+            ),
+        },
+        None => {
+            let title = readme_path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_else(|| "README".to_string());
+            (
+                Some(format!("# {}{}", title, footer)),
+                format!("✅ Created new {} with footer", file_name),
+            )
+        }
+    };
+
+    if matches.get_flag("dry-run") {
+        match &updated {
+            Some(new_content) => {
+                let old_content = existing.unwrap_or_default();
+                let diff = similar::TextDiff::from_lines(&old_content, new_content);
+                print!(
+                    "{}",
+                    diff.unified_diff()
+                        .header(&format!("a/{}", file_name), &format!("b/{}", file_name))
+                );
+                eprintln!("🔍 Dry run, {} was not changed", file_name);
+            }
+            None => println!("{}", message),
+        }
+        return Ok(());
+    }
+
+    if !akshithio_dir.exists() {
+        fs::create_dir_all(&akshithio_dir)?;
+        println!("📁 Created akshithio directory");
+
+        let adot_akshithio_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("akshithio");
+
+        if adot_akshithio_path.exists() {
+            let light_logo_src = adot_akshithio_path.join("light-logo.png");
+            let dark_logo_src = adot_akshithio_path.join("dark-logo.png");
+            let light_logo_dst = akshithio_dir.join("light-logo.png");
+            let dark_logo_dst = akshithio_dir.join("dark-logo.png");
+
+            if light_logo_src.exists() {
+                fs::copy(&light_logo_src, &light_logo_dst)?;
+                println!("📄 Copied light-logo.png");
+            }
+
+            if dark_logo_src.exists() {
+                fs::copy(&dark_logo_src, &dark_logo_dst)?;
+                println!("📄 Copied dark-logo.png");
+            }
+        }
+    }
+
+    if let Some(new_content) = updated {
+        fs::write(&readme_path, new_content)?;
+    }
+    println!("{}", message);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Datelike;
+
+    #[test]
+    fn normalize_content_fixes_line_endings_and_edges() {
+        assert_eq!(
+            normalize_content("\r\n\nfirst  \r\nsecond\t\rthird\n\n"),
+            "first\nsecond\nthird"
+        );
+        assert_eq!(normalize_content("keep\n\n  inner\n"), "keep\n\n  inner");
+        assert_eq!(normalize_content(" \n \n"), "");
+    }
+
+    #[test]
+    fn influx_line_escapes_tag_values() {
+        let location: LocationStruct = serde_json::from_value(serde_json::json!({
+            "city": "New York, NY=1",
+            "region": "",
+            "country": "",
+            "timezone": "America/New_York",
+            "time": { "utc": "1970-01-01T00:00:01Z" },
+            "lat": 40.7,
+            "lon": -74.0,
+        }))
+        .unwrap();
+        assert_eq!(
+            influx_line(&location).unwrap(),
+            "location,city=New\\ York\\,\\ NY\\=1 lat=40.7,lon=-74 1000000000"
+        );
+
+        let unplaced = LocationStruct {
+            lat: None,
+            ..location
+        };
+        assert!(influx_line(&unplaced).is_none());
+    }
+
+    #[test]
+    fn parse_timestamp_reads_every_stored_format() {
+        let expected = "2024-06-01T12:00:00+00:00";
+        for raw in [
+            "2024-06-01T12:00:00Z",
+            "2024-06-01T14:00:00+02:00",
+            "Sat, 01 Jun 2024 12:00:00 +0000",
+            "1717243200",
+            "1717243200000",
+            "1717243200.0",
+            "  1717243200  ",
+        ] {
+            let time = parse_timestamp(raw).unwrap_or_else(|| panic!("could not parse {raw}"));
+            assert_eq!(time.with_timezone(&Utc).to_rfc3339(), expected, "{raw}");
+        }
+        assert!(parse_timestamp("yesterday").is_none());
+        assert!(parse_timestamp("").is_none());
+    }
+
+    #[test]
+    fn parse_timestamp_splits_seconds_and_millis_at_1e11() {
+        // Just under the cutoff is seconds (in the year 5138), at it is milliseconds (1973).
+        assert_eq!(parse_timestamp("99999999999").unwrap().year(), 5138);
+        assert_eq!(parse_timestamp("100000000000").unwrap().year(), 1973);
+    }
+
+    fn post(id: &str, time: &str, visibility: &str, content: &str) -> MicroblogStruct {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "content": content,
+            "time": time,
+            "tags": ["rust"],
+            "visibility": visibility,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn atom_render_includes_public_posts_newest_first() {
+        let feed = atom::Feed {
+            title: "Posts & notes".to_string(),
+            site_url: "https://example.org".to_string(),
+            feed_url: "https://example.org/atom.xml".to_string(),
+            author: "Someone".to_string(),
+        };
+        let mut hidden = post("encrypted", "2024-06-04T00:00:00Z", "public", "v1:abc");
+        hidden.encrypted = true;
+        let posts = [
+            post("old", "2024-06-01T00:00:00Z", "public", "first <post>"),
+            post("secret", "2024-06-02T00:00:00Z", "private", "private post"),
+            post("new", "2024-06-03T00:00:00Z", "public", "second post"),
+            hidden,
+        ];
+
+        let xml = atom::render(&posts, &feed).unwrap();
+        assert!(xml.contains("<title>Posts &amp; notes</title>"));
+        assert!(xml.contains("<updated>2024-06-03T00:00:00+00:00</updated>"));
+        assert!(xml.contains("<id>https://example.org/atom.xml#old</id>"));
+        assert!(xml.contains("first &lt;post&gt;"));
+        assert!(xml.contains("<category term=\"rust\"/>"));
+        assert!(xml.find("second post").unwrap() < xml.find("first &lt;post&gt;").unwrap());
+        assert!(!xml.contains("private post"));
+        assert!(!xml.contains("v1:abc"));
+    }
+
+    #[test]
+    fn atom_render_rejects_bad_feed_settings_and_times() {
+        let feed = |site_url: &str, author: &str| atom::Feed {
+            title: "t".to_string(),
+            site_url: site_url.to_string(),
+            feed_url: "https://example.org/atom.xml".to_string(),
+            author: author.to_string(),
+        };
+        assert!(atom::render(&[], &feed("example.org", "Someone")).is_err());
+        assert!(atom::render(&[], &feed("https://example.org", " ")).is_err());
+
+        let undated = [post("x", "someday", "public", "text")];
+        assert!(atom::render(&undated, &feed("https://example.org", "Someone")).is_err());
+    }
+
+    #[test]
+    fn split_batch_uses_lines_without_a_separator() {
+        assert_eq!(
+            split_batch("one\n\n  two  \nthree", None),
+            [(1, "one"), (3, "two"), (4, "three")]
+        );
+    }
+
+    #[test]
+    fn split_batch_splits_records_on_separator_lines() {
+        let raw = "first\nstill first\n---\n\n  ---  \nsecond\n---\nthird\n---\n";
+        assert_eq!(
+            split_batch(raw, Some("---")),
+            [(1, "first\nstill first"), (6, "second"), (8, "third")]
+        );
+        // Only a whole line matches, not text that contains the separator.
+        assert_eq!(split_batch("a --- b\nc", Some("---")), [(1, "a --- b\nc")]);
+    }
+
+    #[test]
+    fn is_public_ip_rejects_special_ranges() {
+        for ip in [
+            "10.0.0.1",
+            "172.16.0.1",
+            "192.168.1.1",
+            "127.0.0.1",
+            "169.254.0.1",
+            "100.64.0.1",
+            "192.0.2.1",
+            "224.0.0.1",
+            "0.1.2.3",
+            "240.0.0.1",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fc00::1",
+            "fe80::1",
+            "2001:db8::1",
+            "ff02::1",
+            "::ffff:192.168.1.1",
+        ] {
+            assert!(!is_public_ip(&ip.parse().unwrap()), "{ip}");
+        }
+        for ip in [
+            "8.8.8.8",
+            "100.128.0.1",
+            "2606:4700::1111",
+            "::ffff:1.1.1.1",
+        ] {
+            assert!(is_public_ip(&ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn extract_hashtags_collects_lowercase_tags_once() {
+        let (content, tags) = extract_hashtags("Hello #Rust and #rust, #cli-tools- a#b #", false);
+        assert_eq!(content, "Hello #Rust and #rust, #cli-tools- a#b #");
+        assert_eq!(tags, ["rust", "cli-tools"]);
+    }
+
+    #[test]
+    fn extract_hashtags_strips_one_space_per_tag() {
+        assert_eq!(
+            extract_hashtags("shipped #rust today", true).0,
+            "shipped today"
+        );
+        assert_eq!(extract_hashtags("#rust first", true).0, "first");
+        assert_eq!(extract_hashtags("last #rust", true).0, "last");
+        assert_eq!(extract_hashtags("a #x #y b", true).0, "a b");
+        assert_eq!(
+            extract_hashtags("tagged #rust, right", true).0,
+            "tagged, right"
+        );
+    }
+
+    #[test]
+    fn firestore_value_types_every_json_value() {
+        let value = serde_json::json!({
+            "text": "hi",
+            "flag": true,
+            "count": 3,
+            "big": u64::MAX,
+            "ratio": 0.5,
+            "none": null,
+            "tags": ["a"],
+        });
+        assert_eq!(
+            firestore_value(value),
+            serde_json::json!({ "mapValue": { "fields": {
+                "text": { "stringValue": "hi" },
+                "flag": { "booleanValue": true },
+                "count": { "integerValue": "3" },
+                "big": { "integerValue": "18446744073709551615" },
+                "ratio": { "doubleValue": 0.5 },
+                "none": { "nullValue": null },
+                "tags": { "arrayValue": { "values": [{ "stringValue": "a" }] } },
+            } } })
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{parse_timestamp, MicroblogStruct};
+use printpdf::*;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 20.0;
+const LINE_HEIGHT: f32 = 5.5;
+const BODY_SIZE: f32 = 11.0;
+const HEADING_SIZE: f32 = 16.0;
+const WRAP_WIDTH: usize = 85;
+
+struct Cursor {
+    layer: PdfLayerReference,
+    y: f32,
+}
+
+/// Renders the posts as a printable journal with one page (or more, for long days) per day.
+/// Posts are expected in ascending time order. Encrypted posts are listed without their
+/// ciphertext.
+pub fn render_journal(
+    posts: &[MicroblogStruct],
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (doc, page, layer) =
+        PdfDocument::new("adot journal", Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+    let regular = doc.add_builtin_font(BuiltinFont::Helvetica)?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold)?;
+
+    let mut cursor = Cursor {
+        layer: doc.get_page(page).get_layer(layer),
+        y: PAGE_HEIGHT - MARGIN,
+    };
+    let mut current_day: Option<String> = None;
+
+    for post in posts {
+        let (day, clock) = match parse_timestamp(&post.time) {
+            Some(time) => (
+                time.format("%A, %B %-d %Y").to_string(),
+                time.format("%H:%M").to_string(),
+            ),
+            None => (post.time.clone(), String::new()),
+        };
+
+        if current_day.as_deref() != Some(day.as_str()) {
+            if current_day.is_some() {
+                cursor = new_page(&doc);
+            }
+            cursor.layer.use_text(
+                printable(&day),
+                HEADING_SIZE,
+                Mm(MARGIN),
+                Mm(cursor.y),
+                &bold,
+            );
+            cursor.y -= LINE_HEIGHT * 2.0;
+            current_day = Some(day.clone());
+        }
+
+        // The builtin fonts have no lock emoji, and the ciphertext is no use on paper.
+        let content = if post.encrypted {
+            "[encrypted]"
+        } else {
+            post.content.as_str()
+        };
+        let lines = wrap(&printable(content), WRAP_WIDTH);
+        for (index, line) in lines.iter().enumerate() {
+            if cursor.y < MARGIN {
+                cursor = new_page(&doc);
+                cursor.layer.use_text(
+                    format!("{} (continued)", printable(&day)),
+                    HEADING_SIZE,
+                    Mm(MARGIN),
+                    Mm(cursor.y),
+                    &bold,
+                );
+                cursor.y -= LINE_HEIGHT * 2.0;
+            }
+            if index == 0 {
+                cursor
+                    .layer
+                    .use_text(&clock, BODY_SIZE, Mm(MARGIN), Mm(cursor.y), &bold);
+            }
+            cursor
+                .layer
+                .use_text(line, BODY_SIZE, Mm(MARGIN + 15.0), Mm(cursor.y), &regular);
+            cursor.y -= LINE_HEIGHT;
+        }
+        cursor.y -= LINE_HEIGHT / 2.0;
+    }
+
+    doc.save(&mut BufWriter::new(File::create(path)?))?;
+    Ok(())
+}
+
+fn new_page(doc: &PdfDocumentReference) -> Cursor {
+    let (page, layer) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+    Cursor {
+        layer: doc.get_page(page).get_layer(layer),
+        y: PAGE_HEIGHT - MARGIN,
+    }
+}
+
+/// The builtin PDF fonts only cover Latin-1, so anything outside it is replaced.
+fn printable(text: &str) -> String {
+    text.chars()
+        .map(|c| if (c as u32) < 0x100 { c } else { '?' })
+        .collect()
+}
+
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.chars().count() + word.chars().count() + 1 > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        lines.push(line);
+    }
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+    lines
+}
@@ -0,0 +1,70 @@
+use crate::MicroblogStruct;
+use std::fs;
+use std::sync::OnceLock;
+use wasmi::{Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+// Enough for any reasonable transform, small enough that a runaway loop ends quickly.
+const FUEL: u64 = 100_000_000;
+const MAX_MEMORY: usize = 64 * 1024 * 1024;
+
+/// Runs a post through the `transform` export of the module at `path`.
+///
+/// The module gets no imports, so it can't touch the filesystem, network or clock. It must export
+/// `memory`, `alloc(len: i32) -> i32` and `transform(ptr: i32, len: i32) -> i64`: adot writes the
+/// post as JSON into a buffer from `alloc`, and `transform` returns the transformed JSON as
+/// `(ptr << 32) | len`. Every call runs in a fresh instance with bounded fuel and memory.
+pub fn transform(
+    path: &str,
+    post: &MicroblogStruct,
+) -> Result<MicroblogStruct, Box<dyn std::error::Error + Send + Sync>> {
+    static MODULE: OnceLock<Result<(Engine, Module), String>> = OnceLock::new();
+
+    let (engine, module) = MODULE
+        .get_or_init(|| {
+            let bytes =
+                fs::read(path).map_err(|e| format!("Could not read plugin {}: {}", path, e))?;
+            let mut config = Config::default();
+            config.consume_fuel(true);
+            let engine = Engine::new(&config);
+            let module = Module::new(&engine, bytes)
+                .map_err(|e| format!("Invalid plugin {}: {}", path, e))?;
+            Ok((engine, module))
+        })
+        .as_ref()
+        .map_err(|e| e.clone())?;
+
+    let limits = StoreLimitsBuilder::new()
+        .memory_size(MAX_MEMORY)
+        .instances(1)
+        .build();
+    let mut store: Store<StoreLimits> = Store::new(engine, limits);
+    store.limiter(|limits| limits);
+    store.set_fuel(FUEL)?;
+
+    let instance = Linker::new(engine)
+        .instantiate_and_start(&mut store, module)
+        .map_err(|e| format!("Plugin failed to start: {}", e))?;
+    let memory = instance
+        .get_memory(&store, "memory")
+        .ok_or("Plugin does not export `memory`")?;
+    let alloc = instance.get_typed_func::<i32, i32>(&store, "alloc")?;
+    let transform = instance.get_typed_func::<(i32, i32), i64>(&store, "transform")?;
+
+    let input = serde_json::to_vec(post)?;
+    let input_len = i32::try_from(input.len())?;
+    let input_ptr = alloc
+        .call(&mut store, input_len)
+        .map_err(|e| format!("Plugin alloc failed: {}", e))?;
+    memory.write(&mut store, input_ptr as u32 as usize, &input)?;
+
+    let packed = transform
+        .call(&mut store, (input_ptr, input_len))
+        .map_err(|e| format!("Plugin transform failed: {}", e))?;
+    let output_ptr = (packed as u64 >> 32) as usize;
+    let output_len = (packed as u64 & 0xffff_ffff) as usize;
+
+    let mut output = vec![0u8; output_len];
+    memory.read(&store, output_ptr, &mut output)?;
+    serde_json::from_slice(&output)
+        .map_err(|e| format!("Plugin returned an invalid post: {}", e).into())
+}
@@ -0,0 +1,93 @@
+use crate::MicroblogStruct;
+use std::time::Duration;
+
+// Slack rejects section text over 3000 characters.
+const SECTION_LIMIT: usize = 3000;
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Slack reads `<…>` as mentions and links, so `<!channel>` in a post would ping everyone.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn payload(post: &MicroblogStruct) -> serde_json::Value {
+    // Truncate before escaping so an entity is never cut in half.
+    let mut text: String = post.content.chars().take(SECTION_LIMIT).collect();
+    if post.content.chars().count() > SECTION_LIMIT {
+        text.pop();
+        text.push('…');
+    }
+    let mut text = escape(&text);
+    // Escaping can push the text back over the limit.
+    if text.chars().count() > SECTION_LIMIT {
+        text = text.chars().take(SECTION_LIMIT - 1).collect();
+        if let Some(amp) = text.rfind('&').filter(|&i| !text[i..].contains(';')) {
+            text.truncate(amp);
+        }
+        text.push('…');
+    }
+
+    let mut context = post
+        .tags
+        .iter()
+        .map(|tag| format!("#{}", escape(tag)))
+        .collect::<Vec<_>>();
+    context.push(escape(&post.time));
+
+    serde_json::json!({
+        "text": text,
+        "blocks": [
+            {
+                "type": "section",
+                "text": { "type": "mrkdwn", "text": text }
+            },
+            {
+                "type": "context",
+                "elements": [{ "type": "mrkdwn", "text": context.join(" · ") }]
+            }
+        ]
+    })
+}
+
+/// Posts a microblog to a Slack incoming webhook as Block Kit, waiting out `429` responses as
+/// Slack asks via `Retry-After`.
+pub async fn post(
+    webhook_url: &str,
+    post: &MicroblogStruct,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let client = reqwest::Client::new();
+    let body = payload(post);
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        let response = client.post(webhook_url).json(&body).send().await?;
+        let status = response.status();
+
+        if status.is_success() {
+            return Ok(());
+        }
+
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS && attempt < MAX_ATTEMPTS {
+            let wait = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(1);
+            eprintln!("⏳ Slack rate limited the webhook, retrying in {}s", wait);
+            tokio::time::sleep(Duration::from_secs(wait)).await;
+            continue;
+        }
+
+        // Webhooks answer errors with a short code such as `invalid_payload` or `no_service`.
+        let error_text = response.text().await?;
+        return Err(format!(
+            "Slack webhook failed with status {}: {}",
+            status, error_text
+        )
+        .into());
+    }
+
+    Err("Slack webhook kept rate limiting, giving up".into())
+}